@@ -0,0 +1,44 @@
+#![allow(clippy::needless_return)]
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use interp_rs::token::{TokenStream, Tokenizer};
+
+/// A few thousand lines covering every kind of token, most strings plain
+/// and some with escapes
+fn synthetic_source() -> String {
+    let chunk = "\
+mut total: int = 0
+def square(a: int) -> int:
+    return a * a
+for x in [1, 2, 3, 40.5]:
+    if x >= 2 and not done:
+        total += square(x) ** 2 % 7
+    elif x != 3:
+        name = \"plain string\" # trailing comment
+    else:
+        name = \"escaped\\t\\\"string\\\"\\n\"
+value := total |> square
+";
+    return chunk.repeat(500);
+}
+
+fn lex(c: &mut Criterion) {
+    let src = synthetic_source();
+    c.bench_function("tokenize_spanned", |b| {
+        b.iter(|| {
+            let tokens = Tokenizer::new(black_box(src.clone()))
+                .tokenize_spanned()
+                .expect("synthetic source lexes");
+            return tokens.len();
+        })
+    });
+    c.bench_function("token_stream", |b| {
+        b.iter(|| {
+            return TokenStream::new(Tokenizer::new(black_box(src.clone())))
+                .count();
+        })
+    });
+}
+
+criterion_group!(benches, lex);
+criterion_main!(benches);
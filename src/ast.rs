@@ -1,12 +1,191 @@
 use std::any::Any;
-use std::fmt::format;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::num::IntErrorKind;
+use std::rc::Rc;
 
+use crate::eval::{
+    eval_binary, eval_comparison, eval_index, eval_slice, eval_unary,
+    insert_entry, ArgumentError, ControlFlow, Environment, EvalError, Value,
+};
+use crate::format::FormatOptions;
 use crate::parser::ParseError;
-use crate::token::Token;
+use crate::token::{
+    float_literal_value, int_literal_value, Span, StrLiteral, Token,
+};
+use crate::typecheck::{
+    binary_type, unary_type, Type, TypeChecker, TypeError,
+};
 
-pub trait Node: Any {
-    fn repr(&self) -> String;
-    fn eval(self) -> Box<dyn Node>;
+pub trait Node: Any + Debug + NodeClone + NodeEq {
+    /// Source for the node, laid out the way `opts` asks
+    fn format(&self, opts: &FormatOptions) -> String;
+    /// Source for the node in the default layout
+    fn repr(&self) -> String {
+        return self.format(&FormatOptions::default());
+    }
+    /// Node types and nesting, one node per line, for debugging the parser
+    fn tree_repr(&self, depth: usize) -> String;
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError>;
+    /// Infers the node's type where that is possible without running it,
+    /// reporting any type errors found in the node or its children
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type>;
+    /// Where the node came from in the source, for the nodes that keep it
+    fn span(&self) -> Option<Span> {
+        return None;
+    }
+}
+
+/// Lets a `Box<dyn Node>` be cloned, which `Clone` itself can't do as it
+/// isn't object safe. Every node gets this from its Clone impl
+pub trait NodeClone {
+    fn clone_box(&self) -> Box<dyn Node>;
+}
+
+impl<T: Node + Clone> NodeClone for T {
+    fn clone_box(&self) -> Box<dyn Node> {
+        return Box::new(self.clone());
+    }
+}
+
+impl Clone for Box<dyn Node> {
+    fn clone(&self) -> Self {
+        return (**self).clone_box();
+    }
+}
+
+/// Structural equality between nodes, so trees can be compared directly
+/// rather than through a repr that may render different trees the same.
+/// Nodes of different types are never equal
+pub trait NodeEq {
+    fn eq_node(&self, other: &dyn Node) -> bool;
+}
+
+impl<T: Node + PartialEq> NodeEq for T {
+    fn eq_node(&self, other: &dyn Node) -> bool {
+        return (other as &dyn Any)
+            .downcast_ref::<T>()
+            .is_some_and(|other| self == other);
+    }
+}
+
+impl PartialEq for Box<dyn Node> {
+    fn eq(&self, other: &Self) -> bool {
+        return (**self).eq_node(&**other);
+    }
+}
+
+// What derived PartialEq ends up asking for on `Box<dyn Node>` fields,
+// see rust-lang/rust#31740
+impl PartialEq<&Self> for Box<dyn Node> {
+    fn eq(&self, other: &&Self) -> bool {
+        return (**self).eq_node(&***other);
+    }
+}
+
+/// Where a node came from in the source. Equality ignores it, so the same
+/// tree parsed from differently spaced source still compares equal
+#[derive(Debug, Clone, Copy)]
+pub struct NodeSpan(pub Span);
+
+impl PartialEq for NodeSpan {
+    fn eq(&self, _other: &Self) -> bool {
+        return true;
+    }
+}
+
+fn tree_repr_of(
+    depth: usize,
+    label: String,
+    children: &[&dyn Node],
+) -> String {
+    let mut lines = vec![format!("{}{label}", "    ".repeat(depth))];
+    for child in children {
+        lines.push(child.tree_repr(depth + 1));
+    }
+    return lines.join("\n");
+}
+
+/// Quotes a string and undoes the escapes the tokenizer resolved, so the
+/// output lexes back to the same string
+pub fn quote_str(value: &str) -> String {
+    let mut escaped = String::new();
+    for ch in value.chars() {
+        push_escaped(&mut escaped, ch, '"');
+    }
+    return format!("\"{escaped}\"");
+}
+
+/// A char as it would be written in source
+pub fn quote_char(value: char) -> String {
+    let mut escaped = String::new();
+    push_escaped(&mut escaped, value, '\'');
+    return format!("'{escaped}'");
+}
+
+/// Writes `ch` the way the tokenizer reads it back between `quote`s
+fn push_escaped(escaped: &mut String, ch: char, quote: char) {
+    match ch {
+        '\n' => escaped.push_str("\\n"),
+        '\t' => escaped.push_str("\\t"),
+        '\\' => escaped.push_str("\\\\"),
+        '\0' => escaped.push_str("\\0"),
+        _ if ch == quote => {
+            escaped.push('\\');
+            escaped.push(ch);
+        }
+        _ => escaped.push(ch),
+    }
+}
+
+/// Takes a node as the concrete type `T`, handing it back untouched when it
+/// is something else
+fn downcast<T: Node>(node: Box<dyn Node>) -> Result<Box<T>, Box<dyn Node>> {
+    if !(&*node as &dyn Any).is::<T>() {
+        return Err(node);
+    }
+    return Ok((node as Box<dyn Any>)
+        .downcast::<T>()
+        .expect("type was checked above"));
+}
+
+fn int_literal(node: &dyn Node) -> Option<i64> {
+    return (node as &dyn Any)
+        .downcast_ref::<IntegerNode>()
+        .map(|i| i.value);
+}
+
+/// Collapses arithmetic on integer literals into a single literal, so
+/// `2 + 3 * 4` becomes `14`. Anything involving a name, or that wouldn't
+/// evaluate to an integer (dividing by zero, overflow, a negative power),
+/// is left in place for eval to deal with
+pub fn fold_constants(node: Box<dyn Node>) -> Box<dyn Node> {
+    let node = match downcast::<UnaryExpr>(node) {
+        Ok(unary) => {
+            let UnaryExpr { op, operand, span } = *unary;
+            let operand = fold_constants(operand);
+            if let (Token::Sub, Some(i)) = (&op, int_literal(&*operand)) {
+                if let Some(value) = i.checked_neg() {
+                    return Box::new(IntegerNode { value });
+                }
+            }
+            return Box::new(UnaryExpr { op, operand, span });
+        }
+        Err(node) => node,
+    };
+    let node = match downcast::<BinaryExpr>(node) {
+        Ok(binary) => binary,
+        Err(node) => return node,
+    };
+    let BinaryExpr { op, l, r, span } = *node;
+    let (l, r) = (fold_constants(l), fold_constants(r));
+    if let (Some(a), Some(b)) = (int_literal(&*l), int_literal(&*r)) {
+        let folded = eval_binary(&op, Value::Int(a), Value::Int(b));
+        if let Ok(Value::Int(value)) = folded {
+            return Box::new(IntegerNode { value });
+        }
+    }
+    return Box::new(BinaryExpr { op, l, r, span });
 }
 
 pub trait Expression {}
@@ -16,22 +195,30 @@ Expressions:
 1. Binary
 2. Unary
 3. Literal
-4. Walrus?
+4. Walrus
 5. Pipe
 */
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct IntegerNode {
-    value: i32,
+    value: i64,
 }
 
 impl IntegerNode {
     pub fn new(token: Token) -> Result<Self, ParseError> {
         if let Token::Int(i) = token.clone() {
-            return Ok(IntegerNode {
-                value: i
-                    .parse::<i32>()
-                    .map_err(|_| ParseError::InvalidTypeData(format!("")))?,
-            });
+            let value = int_literal_value(&i).map_err(|e| {
+                let reason = match e.kind() {
+                    IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => {
+                        "too large for i64"
+                    }
+                    _ => "is not a number",
+                };
+                ParseError::InvalidTypeData(format!(
+                    "integer literal {i} {reason}"
+                ))
+            })?;
+            return Ok(IntegerNode { value });
         } else {
             return Err(ParseError::InvalidTypeData(format!(
                 "Expected Integer - Found {:?}",
@@ -42,38 +229,712 @@ impl IntegerNode {
 }
 
 impl Node for IntegerNode {
-    fn eval(self) -> Box<dyn Node> {
-        return Box::new(self);
+    fn eval(&self, _env: &mut Environment) -> Result<Value, EvalError> {
+        return Ok(Value::Int(self.value));
     }
-    fn repr(&self) -> String {
+    fn format(&self, _opts: &FormatOptions) -> String {
         return self.value.to_string();
     }
+    fn tree_repr(&self, depth: usize) -> String {
+        return tree_repr_of(
+            depth,
+            format!("IntegerNode({})", self.value),
+            &[],
+        );
+    }
+    fn check_types(&self, _checker: &mut TypeChecker) -> Option<Type> {
+        return Some(Type::Int);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloatNode {
+    value: f64,
+}
+
+impl FloatNode {
+    pub fn new(token: Token) -> Result<Self, ParseError> {
+        if let Token::Float(f) = token.clone() {
+            let value = float_literal_value(&f).map_err(|_| {
+                ParseError::InvalidTypeData(format!(
+                    "float literal {f} is not a number"
+                ))
+            })?;
+            return Ok(FloatNode { value });
+        }
+        return Err(ParseError::InvalidTypeData(format!(
+            "Expected Float - Found {:?}",
+            token
+        )));
+    }
+}
+
+impl Node for FloatNode {
+    fn eval(&self, _env: &mut Environment) -> Result<Value, EvalError> {
+        return Ok(Value::Float(self.value));
+    }
+    fn format(&self, _opts: &FormatOptions) -> String {
+        // Debug keeps the trailing `.0` so whole floats don't read as ints
+        return format!("{:?}", self.value);
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        return tree_repr_of(
+            depth,
+            format!("FloatNode({:?})", self.value),
+            &[],
+        );
+    }
+    fn check_types(&self, _checker: &mut TypeChecker) -> Option<Type> {
+        return Some(Type::Float);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BooleanNode {
+    value: bool,
+}
+
+impl BooleanNode {
+    pub fn new(token: Token) -> Result<Self, ParseError> {
+        return match token {
+            Token::True => Ok(BooleanNode { value: true }),
+            Token::False => Ok(BooleanNode { value: false }),
+            _ => Err(ParseError::InvalidTypeData(format!(
+                "Expected Boolean - Found {:?}",
+                token
+            ))),
+        };
+    }
+}
+
+impl Node for BooleanNode {
+    fn eval(&self, _env: &mut Environment) -> Result<Value, EvalError> {
+        return Ok(Value::Bool(self.value));
+    }
+    fn format(&self, _opts: &FormatOptions) -> String {
+        return self.value.to_string();
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        return tree_repr_of(
+            depth,
+            format!("BooleanNode({})", self.value),
+            &[],
+        );
+    }
+    fn check_types(&self, _checker: &mut TypeChecker) -> Option<Type> {
+        return Some(Type::Bool);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StringNode {
+    value: StrLiteral,
+}
+
+impl StringNode {
+    pub fn new(token: Token) -> Result<Self, ParseError> {
+        if let Token::Str(value) = token {
+            return Ok(StringNode { value });
+        }
+        return Err(ParseError::InvalidTypeData(format!(
+            "Expected String - Found {:?}",
+            token
+        )));
+    }
+}
+
+impl Node for StringNode {
+    fn eval(&self, _env: &mut Environment) -> Result<Value, EvalError> {
+        return Ok(Value::Str(self.value.as_str().to_string()));
+    }
+    fn format(&self, _opts: &FormatOptions) -> String {
+        return quote_str(self.value.as_str());
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        return tree_repr_of(
+            depth,
+            format!("StringNode({:?})", self.value),
+            &[],
+        );
+    }
+    fn check_types(&self, _checker: &mut TypeChecker) -> Option<Type> {
+        return Some(Type::Str);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharNode {
+    value: char,
+}
+
+impl CharNode {
+    pub fn new(token: Token) -> Result<Self, ParseError> {
+        if let Token::Char(value) = token {
+            return Ok(CharNode { value });
+        }
+        return Err(ParseError::InvalidTypeData(format!(
+            "Expected Char - Found {:?}",
+            token
+        )));
+    }
+}
+
+impl Node for CharNode {
+    fn eval(&self, _env: &mut Environment) -> Result<Value, EvalError> {
+        return Ok(Value::Char(self.value));
+    }
+    fn format(&self, _opts: &FormatOptions) -> String {
+        return quote_char(self.value);
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        return tree_repr_of(
+            depth,
+            format!("CharNode({:?})", self.value),
+            &[],
+        );
+    }
+    fn check_types(&self, _checker: &mut TypeChecker) -> Option<Type> {
+        return Some(Type::Char);
+    }
+}
+
+/// The `none` literal, standing for the absence of a value
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoneNode;
+
+impl Node for NoneNode {
+    fn eval(&self, _env: &mut Environment) -> Result<Value, EvalError> {
+        return Ok(Value::None);
+    }
+    fn format(&self, _opts: &FormatOptions) -> String {
+        return Token::None.to_string();
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        return tree_repr_of(depth, "NoneNode".to_string(), &[]);
+    }
+    fn check_types(&self, _checker: &mut TypeChecker) -> Option<Type> {
+        // Any annotation may be left unset with none
+        return None;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListNode {
+    elements: Vec<Box<dyn Node>>,
+}
+
+impl ListNode {
+    pub fn new(elements: Vec<Box<dyn Node>>) -> Self {
+        return ListNode { elements };
+    }
+}
+
+impl Node for ListNode {
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        let values = self
+            .elements
+            .iter()
+            .map(|e| e.eval(env))
+            .collect::<Result<Vec<Value>, EvalError>>()?;
+        return Ok(Value::List(values));
+    }
+    fn format(&self, opts: &FormatOptions) -> String {
+        let elements = self
+            .elements
+            .iter()
+            .map(|e| e.format(opts))
+            .collect::<Vec<String>>()
+            .join(", ");
+        return format!("[{elements}]");
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        let elements: Vec<&dyn Node> =
+            self.elements.iter().map(|e| &**e).collect();
+        return tree_repr_of(depth, "ListNode".to_string(), &elements);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        for element in &self.elements {
+            element.check_types(checker);
+        }
+        return None;
+    }
+}
+
+/// `(a, b)`, fixed once made. One element needs a trailing comma, `(a,)`,
+/// since `(a)` is just `a` in parens
+#[derive(Debug, Clone, PartialEq)]
+pub struct TupleNode {
+    elements: Vec<Box<dyn Node>>,
+}
+
+impl TupleNode {
+    pub fn new(elements: Vec<Box<dyn Node>>) -> Self {
+        return TupleNode { elements };
+    }
+}
+
+impl Node for TupleNode {
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        let values = self
+            .elements
+            .iter()
+            .map(|e| e.eval(env))
+            .collect::<Result<Vec<Value>, EvalError>>()?;
+        return Ok(Value::Tuple(values));
+    }
+    fn format(&self, opts: &FormatOptions) -> String {
+        let elements: Vec<String> =
+            self.elements.iter().map(|e| e.format(opts)).collect();
+        return format_tuple(&elements);
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        let elements: Vec<&dyn Node> =
+            self.elements.iter().map(|e| &**e).collect();
+        return tree_repr_of(depth, "TupleNode".to_string(), &elements);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        for element in &self.elements {
+            element.check_types(checker);
+        }
+        return None;
+    }
+}
+
+/// Writes out a tuple's elements, with the comma a single one needs to
+/// not read as a parenthesised expression
+pub fn format_tuple(elements: &[String]) -> String {
+    if let [element] = elements {
+        return format!("({element},)");
+    }
+    return format!("({})", elements.join(", "));
+}
+
+/// A key and the value it maps to, as written in a map literal
+pub type MapEntry = (Box<dyn Node>, Box<dyn Node>);
+
+/// `{key: value, ...}`. A key written twice keeps the later value
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapNode {
+    entries: Vec<MapEntry>,
+}
+
+impl MapNode {
+    pub fn new(entries: Vec<MapEntry>) -> Self {
+        return MapNode { entries };
+    }
+}
+
+impl Node for MapNode {
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        let mut entries = Vec::new();
+        for (key, value) in &self.entries {
+            let key = key.eval(env)?;
+            insert_entry(&mut entries, key, value.eval(env)?)?;
+        }
+        return Ok(Value::Map(entries));
+    }
+    fn format(&self, opts: &FormatOptions) -> String {
+        let entries = self
+            .entries
+            .iter()
+            .map(|(key, value)| {
+                format!("{}: {}", key.format(opts), value.format(opts))
+            })
+            .collect::<Vec<String>>()
+            .join(", ");
+        return format!("{{{entries}}}");
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        let children: Vec<&dyn Node> = self
+            .entries
+            .iter()
+            .flat_map(|(key, value)| [&**key, &**value])
+            .collect();
+        return tree_repr_of(depth, "MapNode".to_string(), &children);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        for (key, value) in &self.entries {
+            key.check_types(checker);
+            value.check_types(checker);
+        }
+        return None;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexExpr {
+    target: Box<dyn Node>,
+    index: Box<dyn Node>,
+    // Where the opening bracket is
+    span: NodeSpan,
+}
+
+impl IndexExpr {
+    pub fn new(
+        target: Box<dyn Node>,
+        index: Box<dyn Node>,
+        span: Span,
+    ) -> Self {
+        return IndexExpr {
+            target,
+            index,
+            span: NodeSpan(span),
+        };
+    }
+}
+
+impl Node for IndexExpr {
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        let target = self.target.eval(env)?;
+        return eval_index(target, self.index.eval(env)?);
+    }
+    fn format(&self, opts: &FormatOptions) -> String {
+        let target = self.target.format(opts);
+        return format!("{target}[{}]", self.index.format(opts));
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        let children: [&dyn Node; 2] = [&*self.target, &*self.index];
+        return tree_repr_of(depth, "IndexExpr".to_string(), &children);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        self.target.check_types(checker);
+        self.index.check_types(checker);
+        return None;
+    }
+    fn span(&self) -> Option<Span> {
+        return Some(self.span.0);
+    }
+}
+
+/// `target[start:stop:step]`, where any of the three may be left out
+#[derive(Debug, Clone, PartialEq)]
+pub struct SliceExpr {
+    target: Box<dyn Node>,
+    start: Option<Box<dyn Node>>,
+    stop: Option<Box<dyn Node>>,
+    step: Option<Box<dyn Node>>,
+    // Where the opening bracket is
+    span: NodeSpan,
+}
+
+impl SliceExpr {
+    pub fn new(
+        target: Box<dyn Node>,
+        start: Option<Box<dyn Node>>,
+        stop: Option<Box<dyn Node>>,
+        step: Option<Box<dyn Node>>,
+        span: Span,
+    ) -> Self {
+        return SliceExpr {
+            target,
+            start,
+            stop,
+            step,
+            span: NodeSpan(span),
+        };
+    }
+    fn bounds(&self) -> [Option<&dyn Node>; 3] {
+        return [&self.start, &self.stop, &self.step].map(|b| b.as_deref());
+    }
+}
+
+impl Node for SliceExpr {
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        let target = self.target.eval(env)?;
+        let mut bounds = [None; 3];
+        for (bound, node) in bounds.iter_mut().zip(self.bounds()) {
+            *bound = match node.map(|node| node.eval(env)).transpose()? {
+                // Like Python, none is the same as leaving the bound out
+                None | Some(Value::None) => None,
+                Some(Value::Int(i)) => Some(i),
+                Some(other) => {
+                    return Err(EvalError::InvalidOperand(format!(
+                        "Expected Integer - Found {}",
+                        other.repr()
+                    )));
+                }
+            };
+        }
+        let [start, stop, step] = bounds;
+        return eval_slice(target, start, stop, step);
+    }
+    fn format(&self, opts: &FormatOptions) -> String {
+        let [start, stop, step] = self
+            .bounds()
+            .map(|bound| bound.map(|b| b.format(opts)).unwrap_or_default());
+        let target = self.target.format(opts);
+        if self.step.is_none() {
+            return format!("{target}[{start}:{stop}]");
+        }
+        return format!("{target}[{start}:{stop}:{step}]");
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        let mut children: Vec<&dyn Node> = vec![&*self.target];
+        children.extend(self.bounds().into_iter().flatten());
+        return tree_repr_of(depth, "SliceExpr".to_string(), &children);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        let target = self.target.check_types(checker);
+        for bound in self.bounds().into_iter().flatten() {
+            bound.check_types(checker);
+        }
+        // Slicing a string gives a string
+        return target.filter(|&ty| ty == Type::Str);
+    }
+    fn span(&self) -> Option<Span> {
+        return Some(self.span.0);
+    }
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct BinaryExpr {
     op: Token,
     l: Box<dyn Node>,
     r: Box<dyn Node>,
+    // Where the operator is
+    span: NodeSpan,
 }
 
 impl BinaryExpr {
-    pub fn new(op_token: Token, l: Box<dyn Node>, r: Box<dyn Node>) -> Self {
+    pub fn new(
+        op_token: Token,
+        l: Box<dyn Node>,
+        r: Box<dyn Node>,
+        span: Span,
+    ) -> Self {
         return BinaryExpr {
             op: op_token,
-            l: l,
-            r: r,
+            l,
+            r,
+            span: NodeSpan(span),
         };
     }
 }
 
 impl Node for BinaryExpr {
-    fn eval(self) -> Box<dyn Node> {
-        return Box::new(self);
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        let (l, r) = (self.l.eval(env)?, self.r.eval(env)?);
+        return eval_binary(&self.op, l, r);
     }
-    fn repr(&self) -> String {
-        let l = &self.l.repr();
-        let r = &self.r.repr();
-        return format!("({} {} {})", l, self.op, r);
+    fn format(&self, opts: &FormatOptions) -> String {
+        let (mut l, r) = (self.l.format(opts), self.r.format(opts));
+        // A negative literal base needs brackets, or `(-2) ** 2` would read
+        // back as `-(2 ** 2)`
+        if self.op == Token::Pow && l.starts_with('-') {
+            l = format!("({l})");
+        }
+        return format!("({l}{}{r})", opts.op(&self.op));
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        let label = format!("BinaryExpr({})", self.op);
+        return tree_repr_of(depth, label, &[&*self.l, &*self.r]);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        let l = self.l.check_types(checker);
+        let r = self.r.check_types(checker);
+        return binary_type(checker, &self.op, l, r);
+    }
+    fn span(&self) -> Option<Span> {
+        return Some(self.span.0);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnaryExpr {
+    op: Token,
+    operand: Box<dyn Node>,
+    // Where the operator is
+    span: NodeSpan,
+}
+
+impl UnaryExpr {
+    pub fn new(op_token: Token, operand: Box<dyn Node>, span: Span) -> Self {
+        return UnaryExpr {
+            op: op_token,
+            operand,
+            span: NodeSpan(span),
+        };
+    }
+}
+
+impl Node for UnaryExpr {
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        return eval_unary(&self.op, self.operand.eval(env)?);
+    }
+    fn format(&self, opts: &FormatOptions) -> String {
+        return match self.op {
+            // Keyword operators need a space before their operand
+            Token::Not => {
+                format!("({} {})", self.op, self.operand.format(opts))
+            }
+            _ => format!("({}{})", self.op, self.operand.format(opts)),
+        };
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        let label = format!("UnaryExpr({})", self.op);
+        return tree_repr_of(depth, label, &[&*self.operand]);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        let operand = self.operand.check_types(checker);
+        return unary_type(checker, &self.op, operand);
+    }
+    fn span(&self) -> Option<Span> {
+        return Some(self.span.0);
+    }
+}
+
+/// `and`/`or` are kept apart from BinaryExpr since they only evaluate their
+/// right side when the left side doesn't already decide the result
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalExpr {
+    op: Token,
+    l: Box<dyn Node>,
+    r: Box<dyn Node>,
+    // Where the operator is
+    span: NodeSpan,
+}
+
+impl LogicalExpr {
+    pub fn new(
+        op_token: Token,
+        l: Box<dyn Node>,
+        r: Box<dyn Node>,
+        span: Span,
+    ) -> Self {
+        return LogicalExpr {
+            op: op_token,
+            l,
+            r,
+            span: NodeSpan(span),
+        };
+    }
+}
+
+impl Node for LogicalExpr {
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        let l = self.l.eval(env)?.truthy()?;
+        // `false and x` and `true or x` are settled without looking at x
+        if l == (self.op == Token::Or) {
+            return Ok(Value::Bool(l));
+        }
+        return Ok(Value::Bool(self.r.eval(env)?.truthy()?));
+    }
+    fn format(&self, opts: &FormatOptions) -> String {
+        return format!(
+            "({} {} {})",
+            self.l.format(opts),
+            self.op,
+            self.r.format(opts)
+        );
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        let label = format!("LogicalExpr({})", self.op);
+        return tree_repr_of(depth, label, &[&*self.l, &*self.r]);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        self.l.check_types(checker);
+        self.r.check_types(checker);
+        return Some(Type::Bool);
+    }
+    fn span(&self) -> Option<Span> {
+        return Some(self.span.0);
+    }
+}
+
+/// `then if cond else otherwise`, which evaluates only the side the
+/// condition picks
+#[derive(Debug, Clone, PartialEq)]
+pub struct TernaryExpr {
+    then: Box<dyn Node>,
+    cond: Box<dyn Node>,
+    otherwise: Box<dyn Node>,
+    // Where the `if` is
+    span: NodeSpan,
+}
+
+impl TernaryExpr {
+    pub fn new(
+        then: Box<dyn Node>,
+        cond: Box<dyn Node>,
+        otherwise: Box<dyn Node>,
+        span: Span,
+    ) -> Self {
+        return TernaryExpr {
+            then,
+            cond,
+            otherwise,
+            span: NodeSpan(span),
+        };
+    }
+}
+
+impl Node for TernaryExpr {
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        if self.cond.eval(env)?.truthy()? {
+            return self.then.eval(env);
+        }
+        return self.otherwise.eval(env);
+    }
+    fn format(&self, opts: &FormatOptions) -> String {
+        return format!(
+            "({} {} {} {} {})",
+            self.then.format(opts),
+            Token::If,
+            self.cond.format(opts),
+            Token::Else,
+            self.otherwise.format(opts)
+        );
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        let children: [&dyn Node; 3] =
+            [&*self.then, &*self.cond, &*self.otherwise];
+        return tree_repr_of(depth, "TernaryExpr".to_string(), &children);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        self.cond.check_types(checker);
+        let then = self.then.check_types(checker);
+        let otherwise = self.otherwise.check_types(checker);
+        // Known only when both sides agree
+        return then.filter(|_| then == otherwise);
+    }
+    fn span(&self) -> Option<Span> {
+        return Some(self.span.0);
+    }
+}
+
+/// `name := expr` binds like an assignment but, being an expression, also
+/// produces the bound value
+#[derive(Debug, Clone, PartialEq)]
+pub struct WalrusExpr {
+    identifier: Identifier,
+    expr: Box<dyn Node>,
+}
+
+impl WalrusExpr {
+    pub fn new(identifier: Identifier, expr: Box<dyn Node>) -> Self {
+        return WalrusExpr { identifier, expr };
+    }
+}
+
+impl Node for WalrusExpr {
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        let value = self.expr.eval(env)?;
+        env.assign(self.identifier.literal.clone(), value.clone())?;
+        return Ok(value);
+    }
+    fn format(&self, opts: &FormatOptions) -> String {
+        let name = &self.identifier.literal;
+        let expr = self.expr.format(opts);
+        return format!("({name}{}{expr})", opts.op(&Token::Walrus));
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        let label = format!("WalrusExpr({})", self.identifier.literal);
+        return tree_repr_of(depth, label, &[&*self.expr]);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        let ty = self.expr.check_types(checker);
+        checker.set(self.identifier.literal.clone(), ty);
+        return ty;
     }
 }
 
@@ -83,95 +944,687 @@ Statements:
 2. Block
 3. If
 4. Return
-5. Switch
+5. Match
 6. Fn declaration
 */
 
-#[derive(Debug)]
-pub struct Identifier {
-    literal: String,
+#[derive(Debug, Clone, PartialEq)]
+pub struct Identifier {
+    literal: String,
+}
+impl Identifier {
+    pub fn new(literal: String) -> Self {
+        return Identifier { literal };
+    }
+}
+
+impl Node for Identifier {
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        return env
+            .get(&self.literal)
+            .cloned()
+            .ok_or_else(|| EvalError::Undefined(self.literal.clone()));
+    }
+    fn format(&self, _opts: &FormatOptions) -> String {
+        return self.literal.clone();
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        return tree_repr_of(
+            depth,
+            format!("Identifier({})", self.literal),
+            &[],
+        );
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        return checker.get(&self.literal);
+    }
+}
+
+/// `name = value` in a call, binding an argument by its parameter's name
+pub type NamedArg = (Identifier, Box<dyn Node>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallStmt {
+    name: Identifier,
+    args: Vec<Box<dyn Node>>,
+    // After every positional argument, as they have to be written
+    named: Vec<NamedArg>,
+    // Where the function name is, or the `|>` for a bare name piped into
+    span: NodeSpan,
+}
+impl CallStmt {
+    pub fn new(
+        name: Identifier,
+        args: Vec<Box<dyn Node>>,
+        named: Vec<NamedArg>,
+        span: Span,
+    ) -> Self {
+        return CallStmt {
+            name,
+            args,
+            named,
+            span: NodeSpan(span),
+        };
+    }
+
+    /// Desugars `arg |> target`, where the target is either a bare function
+    /// name or a call whose remaining arguments follow the piped one
+    pub fn from_pipe(
+        arg: Box<dyn Node>,
+        target: Box<dyn Node>,
+        pipe_span: Span,
+    ) -> Result<Self, ParseError> {
+        let target = match downcast::<Identifier>(target) {
+            Ok(name) => {
+                let args = vec![arg];
+                return Ok(CallStmt::new(*name, args, Vec::new(), pipe_span));
+            }
+            Err(target) => target,
+        };
+        let target = match downcast::<CallStmt>(target) {
+            Ok(call) => call,
+            Err(target) => {
+                return Err(ParseError::InvalidTokenOrder(format!(
+                    "Expected Function After {} - Found {}",
+                    Token::Pipe,
+                    target.repr()
+                )));
+            }
+        };
+        let CallStmt {
+            name,
+            mut args,
+            named,
+            span,
+        } = *target;
+        args.insert(0, arg);
+        return Ok(CallStmt::new(name, args, named, span.0));
+    }
+}
+impl CallStmt {
+    /// Evaluates the arguments where the call is, lined up with the
+    /// parameters they're for
+    fn eval_args(
+        &self,
+        params: &[FnArg],
+        env: &mut Environment,
+    ) -> Result<Vec<Option<Value>>, EvalError> {
+        let name = &self.name.literal;
+        check_arity(name, params, self.args.len())?;
+        let mut values: Vec<Option<Value>> = vec![None; params.len()];
+        for (slot, e) in values.iter_mut().zip(&self.args) {
+            *slot = Some(e.eval(env)?);
+        }
+        for (arg_name, e) in &self.named {
+            let arg = &arg_name.literal;
+            let argument_error = |reason| EvalError::InvalidArgument {
+                name: name.clone(),
+                arg: arg.clone(),
+                reason,
+            };
+            let Some(ix) = params.iter().position(|a| a.name.literal == *arg)
+            else {
+                return Err(argument_error(ArgumentError::Unknown));
+            };
+            if values[ix].is_some() {
+                return Err(argument_error(ArgumentError::Repeated));
+            }
+            values[ix] = Some(e.eval(env)?);
+        }
+        check_missing(name, params, &values)?;
+        return Ok(values);
+    }
+}
+
+/// The parameters of a function value, or None for builtins, which check
+/// their own arguments
+fn params_of(callee: &Value) -> Option<&[FnArg]> {
+    return match callee {
+        Value::Fn(function) => Some(&function.args),
+        Value::Lambda(closure) => Some(&closure.lambda.args),
+        Value::Struct(def) => Some(&def.fields),
+        _ => None,
+    };
+}
+
+fn check_arity(
+    name: &str,
+    params: &[FnArg],
+    found: usize,
+) -> Result<(), EvalError> {
+    if found > params.len() {
+        return Err(EvalError::ArityMismatch {
+            name: name.to_string(),
+            expected: params.len(),
+            found,
+        });
+    }
+    return Ok(());
+}
+
+/// Errors if a parameter without a default was given no value
+fn check_missing(
+    name: &str,
+    params: &[FnArg],
+    values: &[Option<Value>],
+) -> Result<(), EvalError> {
+    let missing = params
+        .iter()
+        .zip(values)
+        .find(|(arg, value)| value.is_none() && !arg.has_default());
+    if let Some((arg, _)) = missing {
+        return Err(EvalError::InvalidArgument {
+            name: name.to_string(),
+            arg: arg.name.literal.clone(),
+            reason: ArgumentError::Missing,
+        });
+    }
+    return Ok(());
+}
+
+/// Calls a function value with arguments that are already evaluated, the
+/// way builtins like map call the function they are given
+pub fn call_value(
+    callee: &Value,
+    args: Vec<Value>,
+    env: &mut Environment,
+) -> Result<Value, EvalError> {
+    if let Value::Builtin(builtin) = callee {
+        return builtin.call(args, env);
+    }
+    let name = callee.to_string();
+    let Some(params) = params_of(callee) else {
+        return Err(EvalError::NotCallable(callee.repr()));
+    };
+    check_arity(&name, params, args.len())?;
+    let mut values: Vec<Option<Value>> = args.into_iter().map(Some).collect();
+    values.resize(params.len(), None);
+    check_missing(&name, params, &values)?;
+    return call_with(callee, values, env);
+}
+
+/// Runs a function or lambda's body in a scope of its own, with `values`
+/// from eval_args bound to its parameters
+fn call_with(
+    callee: &Value,
+    values: Vec<Option<Value>>,
+    env: &mut Environment,
+) -> Result<Value, EvalError> {
+    if let Value::Struct(def) = callee {
+        // Fields have no defaults, so check_missing has seen to it that
+        // every one has a value
+        let fields = values.into_iter().flatten().collect();
+        return Ok(Value::Instance(Rc::new(Instance {
+            def: Rc::clone(def),
+            fields,
+        })));
+    }
+    env.check_recursion_limit()?;
+    return env.scoped(|env| match callee {
+        Value::Fn(function) => {
+            // A function can always call itself, even one defined inside
+            // another whose scope the call can't see
+            let itself = Value::Fn(Rc::clone(function));
+            env.declare(function.name.literal.clone(), itself, false);
+            declare_args(&function.args, values, env)?;
+            // Running off the end of the body returns nothing
+            return match function.definition.eval(env) {
+                Ok(_) => Ok(Value::Unit),
+                Err(EvalError::ControlFlow(ControlFlow::Return(value))) => {
+                    Ok(value)
+                }
+                Err(e) => Err(e),
+            };
+        }
+        Value::Lambda(closure) => {
+            for (name, value) in &closure.captured {
+                env.declare(name.clone(), value.clone(), false);
+            }
+            declare_args(&closure.lambda.args, values, env)?;
+            return closure.lambda.body.eval(env);
+        }
+        _ => unreachable!("only functions have parameters"),
+    });
+}
+
+/// Binds each parameter to its value from eval_args, or else its default.
+/// Defaults are evaluated in the call's scope, so can refer to the
+/// arguments before them
+fn declare_args(
+    params: &[FnArg],
+    values: Vec<Option<Value>>,
+    env: &mut Environment,
+) -> Result<(), EvalError> {
+    for (arg, value) in params.iter().zip(values) {
+        let value = match (value, &arg.default) {
+            (Some(value), _) => value,
+            (None, Some(default)) => default.eval(env)?,
+            (None, None) => unreachable!("checked by check_missing"),
+        };
+        env.declare(arg.name.literal.clone(), value, false);
+    }
+    return Ok(());
+}
+
+impl Node for CallStmt {
+    /// Evaluates the arguments where the call is, then the function's body
+    /// in a scope of its own with the arguments bound to its parameters
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        let name = &self.name.literal;
+        let Some(callee) = env.get(name).cloned() else {
+            return Err(EvalError::Undefined(name.clone()));
+        };
+        if let Value::Builtin(builtin) = callee {
+            // Builtin parameters have no names to bind by
+            if let Some((arg, _)) = self.named.first() {
+                return Err(EvalError::InvalidArgument {
+                    name: name.clone(),
+                    arg: arg.literal.clone(),
+                    reason: ArgumentError::Unknown,
+                });
+            }
+            let args = self
+                .args
+                .iter()
+                .map(|e| e.eval(env))
+                .collect::<Result<Vec<Value>, EvalError>>()?;
+            return builtin.call(args, env);
+        }
+        let Some(params) = params_of(&callee) else {
+            return Err(EvalError::NotCallable(name.clone()));
+        };
+        let values = self.eval_args(params, env)?;
+        return call_with(&callee, values, env);
+    }
+    fn format(&self, opts: &FormatOptions) -> String {
+        let op = opts.op(&Token::Assignment);
+        let named = self.named.iter().map(|(name, e)| {
+            return format!("{}{op}{}", name.literal, e.format(opts));
+        });
+        let args = self
+            .args
+            .iter()
+            .map(|e| e.format(opts))
+            .chain(named)
+            .collect::<Vec<String>>()
+            .join(", ");
+        return format!("{}({})", self.name.literal, args);
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        let named = self.named.iter().map(|(_, e)| &**e);
+        let args: Vec<&dyn Node> =
+            self.args.iter().map(|e| &**e).chain(named).collect();
+        let mut label = self.name.literal.clone();
+        if !self.named.is_empty() {
+            let names: Vec<&str> =
+                self.named.iter().map(|(n, _)| n.literal.as_str()).collect();
+            label = format!("{label}, named {}", names.join(", "));
+        }
+        return tree_repr_of(depth, format!("CallStmt({label})"), &args);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        let named = self.named.iter().map(|(_, e)| e);
+        for arg in self.args.iter().chain(named) {
+            arg.check_types(checker);
+        }
+        return None;
+    }
+    fn span(&self) -> Option<Span> {
+        return Some(self.span.0);
+    }
+}
+
+/// What runs when an `if` condition is false
+#[derive(Debug, Clone, PartialEq)]
+pub enum ElseBranch {
+    Elif(Box<ConditionalStmt>),
+    Else(Box<BlockStmt>),
+}
+
+impl ElseBranch {
+    fn node(&self) -> &dyn Node {
+        return match self {
+            ElseBranch::Elif(elif) => &**elif,
+            ElseBranch::Else(fail_block) => &**fail_block,
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConditionalStmt {
+    // Level the `if` itself sits at, which its `elif` and `else` must line
+    // up with
+    indent: usize,
+    conditional: Box<dyn Node>,
+    pass_block: Box<BlockStmt>,
+    fail_branch: Option<ElseBranch>,
+}
+
+impl ConditionalStmt {
+    pub fn new(
+        indent: usize,
+        cond: Box<dyn Node>,
+        pass_block: Box<BlockStmt>,
+        fail_branch: Option<ElseBranch>,
+    ) -> Self {
+        return ConditionalStmt {
+            indent,
+            conditional: cond,
+            pass_block,
+            fail_branch,
+        };
+    }
+    /// Source for the branch, introduced by `keyword`, which is `elif` for
+    /// all but the first in a chain
+    fn format_branch(&self, keyword: Token, opts: &FormatOptions) -> String {
+        let spaces = opts.indent(self.indent);
+        let else_ = match &self.fail_branch {
+            Some(ElseBranch::Elif(elif)) => {
+                format!("\n{spaces}{}", elif.format_branch(Token::Elif, opts))
+            }
+            Some(ElseBranch::Else(fail_block)) => format!(
+                "\n{spaces}{}:\n{}",
+                Token::Else,
+                fail_block.format(opts)
+            ),
+            None => "".to_string(),
+        };
+        // Keywords come from Token's Display so repr always spells them the
+        // way the tokenizer reads them
+        return format!(
+            "{keyword} {}:\n{}{else_}",
+            self.conditional.format(opts),
+            self.pass_block.format(opts),
+        );
+    }
+}
+
+impl Node for ConditionalStmt {
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        if self.conditional.eval(env)?.truthy()? {
+            return self.pass_block.eval(env);
+        }
+        return match &self.fail_branch {
+            Some(fail_branch) => fail_branch.node().eval(env),
+            None => Ok(Value::Unit),
+        };
+    }
+    fn format(&self, opts: &FormatOptions) -> String {
+        return self.format_branch(Token::If, opts);
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        let mut children: Vec<&dyn Node> =
+            vec![&*self.conditional, &*self.pass_block];
+        if let Some(fail_branch) = &self.fail_branch {
+            children.push(fail_branch.node());
+        }
+        return tree_repr_of(depth, "ConditionalStmt".to_string(), &children);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        self.conditional.check_types(checker);
+        self.pass_block.check_types(checker);
+        if let Some(fail_branch) = &self.fail_branch {
+            fail_branch.node().check_types(checker);
+        }
+        return None;
+    }
+}
+
+/// What a `case` in a match compares against
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Literal(Box<dyn Node>),
+    // `_`, which matches anything
+    Wildcard,
+}
+
+impl Pattern {
+    fn format(&self, opts: &FormatOptions) -> String {
+        return match self {
+            Pattern::Literal(literal) => literal.format(opts),
+            Pattern::Wildcard => "_".to_string(),
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchStmt {
+    // Level the `match` sits at, its cases are one deeper
+    indent: usize,
+    scrutinee: Box<dyn Node>,
+    arms: Vec<(Pattern, Box<BlockStmt>)>,
 }
-impl Identifier {
-    pub fn new(literal: String) -> Self {
-        return Identifier { literal };
+
+impl MatchStmt {
+    pub fn new(
+        indent: usize,
+        scrutinee: Box<dyn Node>,
+        arms: Vec<(Pattern, Box<BlockStmt>)>,
+    ) -> Self {
+        return MatchStmt {
+            indent,
+            scrutinee,
+            arms,
+        };
     }
 }
 
-impl Node for Identifier {
-    fn eval(self) -> Box<dyn Node> {
-        return Box::new(self);
+impl Node for MatchStmt {
+    /// Runs the first arm whose pattern equals the scrutinee, or nothing if
+    /// none do. Patterns compare as `==` does, so `case 1:` matches `1.0`,
+    /// except that one of another type doesn't match rather than failing
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        let value = self.scrutinee.eval(env)?;
+        for (pattern, body) in &self.arms {
+            let matches = match pattern {
+                Pattern::Literal(literal) => {
+                    let literal = literal.eval(env)?;
+                    match eval_comparison(&Token::Eq, &value, &literal) {
+                        Ok(equal) => equal == Value::Bool(true),
+                        Err(EvalError::TypeMismatch { .. }) => false,
+                        Err(e) => return Err(e),
+                    }
+                }
+                Pattern::Wildcard => true,
+            };
+            if matches {
+                return body.eval(env);
+            }
+        }
+        return Ok(Value::Unit);
     }
-    fn repr(&self) -> String {
-        return format!("{}", self.literal);
+    fn format(&self, opts: &FormatOptions) -> String {
+        let spaces = opts.indent(self.indent + 1);
+        let arms = self
+            .arms
+            .iter()
+            .map(|(pattern, body)| {
+                format!(
+                    "{spaces}{} {}:\n{}",
+                    Token::Case,
+                    pattern.format(opts),
+                    body.format(opts)
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+        let scrutinee = self.scrutinee.format(opts);
+        return format!("{} {scrutinee}:\n{arms}", Token::Match);
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        let mut children: Vec<&dyn Node> = vec![&*self.scrutinee];
+        for (pattern, body) in &self.arms {
+            if let Pattern::Literal(literal) = pattern {
+                children.push(&**literal);
+            }
+            children.push(&**body);
+        }
+        return tree_repr_of(depth, "MatchStmt".to_string(), &children);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        self.scrutinee.check_types(checker);
+        for (pattern, body) in &self.arms {
+            if let Pattern::Literal(literal) = pattern {
+                literal.check_types(checker);
+            }
+            body.check_types(checker);
+        }
+        return None;
     }
 }
 
-pub struct CallStmt {
-    name: Identifier,
-    args: Vec<Box<dyn Node>>,
+/// Runs one pass of a loop body, catching a `break` or `continue` that
+/// cuts it short. Gives false when the loop should stop
+fn run_iteration(
+    body: &BlockStmt,
+    env: &mut Environment,
+) -> Result<bool, EvalError> {
+    return match body.eval(env) {
+        Err(EvalError::ControlFlow(ControlFlow::Break)) => Ok(false),
+        Err(EvalError::ControlFlow(ControlFlow::Continue)) | Ok(_) => Ok(true),
+        Err(e) => Err(e),
+    };
 }
-impl CallStmt {
-    pub fn new(name: Identifier, args: Vec<Box<dyn Node>>) -> Self {
-        return CallStmt { name, args };
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WhileStmt {
+    cond: Box<dyn Node>,
+    body: Box<BlockStmt>,
+}
+
+impl WhileStmt {
+    pub fn new(cond: Box<dyn Node>, body: Box<BlockStmt>) -> Self {
+        return WhileStmt { cond, body };
     }
 }
-impl Node for CallStmt {
-    fn eval(self) -> Box<dyn Node> {
-        return Box::new(self);
+
+impl Node for WhileStmt {
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        let mut iterations = 0;
+        while self.cond.eval(env)?.truthy()? {
+            env.check_loop_limit(iterations)?;
+            if !run_iteration(&self.body, env)? {
+                break;
+            }
+            iterations += 1;
+        }
+        return Ok(Value::Unit);
     }
-    fn repr(&self) -> String {
-        let args = self
-            .args
-            .iter()
-            .map(|e| e.repr())
-            .collect::<Vec<String>>()
-            .join(", ");
-        return format!("{}({})", self.name.literal, args);
+    fn format(&self, opts: &FormatOptions) -> String {
+        return format!(
+            "{} {}:\n{}",
+            Token::While,
+            self.cond.format(opts),
+            self.body.format(opts),
+        );
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        let children: [&dyn Node; 2] = [&*self.cond, &*self.body];
+        return tree_repr_of(depth, "WhileStmt".to_string(), &children);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        self.cond.check_types(checker);
+        self.body.check_types(checker);
+        return None;
     }
 }
 
-pub struct ConditionalStmt {
-    conditional: Box<dyn Node>,
-    pass_block: Box<BlockStmt>,
-    fail_block: Option<Box<BlockStmt>>,
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForStmt {
+    var: Identifier,
+    iterable: Box<dyn Node>,
+    body: Box<BlockStmt>,
 }
 
-impl ConditionalStmt {
+impl ForStmt {
     pub fn new(
-        cond: Box<dyn Node>,
-        pass_block: Box<BlockStmt>,
-        fail_block: Option<Box<BlockStmt>>,
+        var: Identifier,
+        iterable: Box<dyn Node>,
+        body: Box<BlockStmt>,
     ) -> Self {
-        return ConditionalStmt {
-            conditional: cond,
-            pass_block,
-            fail_block,
+        return ForStmt {
+            var,
+            iterable,
+            body,
         };
     }
 }
 
-impl Node for ConditionalStmt {
-    fn eval(self) -> Box<dyn Node> {
-        return Box::new(self);
-    }
-    fn repr(&self) -> String {
-        let mut else_ = "".to_string();
-        if self.fail_block.is_some() {
-            let spaces =
-                "    ".repeat(self.fail_block.as_ref().unwrap().indent - 1);
-            else_ = format!(
-                "{spaces}else:\n{}",
-                self.fail_block.as_ref().unwrap().repr()
-            );
+impl Node for ForStmt {
+    /// Runs the body once per element, with the element bound to the loop
+    /// variable afresh each time
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        let values = self.iterable.eval(env)?.into_list()?;
+        for (iterations, value) in values.into_iter().enumerate() {
+            env.check_loop_limit(iterations)?;
+            env.declare(self.var.literal.clone(), value, false);
+            if !run_iteration(&self.body, env)? {
+                break;
+            }
         }
+        return Ok(Value::Unit);
+    }
+    fn format(&self, opts: &FormatOptions) -> String {
         return format!(
-            "if ({}):\n{}\n{else_}",
-            self.conditional.repr(),
-            self.pass_block.repr(),
+            "{} {} {} {}:\n{}",
+            Token::For,
+            self.var.literal,
+            Token::In,
+            self.iterable.format(opts),
+            self.body.format(opts),
         );
     }
+    fn tree_repr(&self, depth: usize) -> String {
+        let label = format!("ForStmt({})", self.var.literal);
+        let children: [&dyn Node; 2] = [&*self.iterable, &*self.body];
+        return tree_repr_of(depth, label, &children);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        self.iterable.check_types(checker);
+        // Lists aren't typed, so nothing is known about the elements
+        checker.set(self.var.literal.clone(), None);
+        self.body.check_types(checker);
+        return None;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BreakStmt;
+
+impl Node for BreakStmt {
+    fn eval(&self, _env: &mut Environment) -> Result<Value, EvalError> {
+        return Err(EvalError::ControlFlow(ControlFlow::Break));
+    }
+    fn format(&self, _opts: &FormatOptions) -> String {
+        return Token::Break.to_string();
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        return tree_repr_of(depth, "BreakStmt".to_string(), &[]);
+    }
+    fn check_types(&self, _checker: &mut TypeChecker) -> Option<Type> {
+        return None;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContinueStmt;
+
+impl Node for ContinueStmt {
+    fn eval(&self, _env: &mut Environment) -> Result<Value, EvalError> {
+        return Err(EvalError::ControlFlow(ControlFlow::Continue));
+    }
+    fn format(&self, _opts: &FormatOptions) -> String {
+        return Token::Continue.to_string();
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        return tree_repr_of(depth, "ContinueStmt".to_string(), &[]);
+    }
+    fn check_types(&self, _checker: &mut TypeChecker) -> Option<Type> {
+        return None;
+    }
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct ReturnStmt {
     expr: Box<dyn Node>,
 }
@@ -183,39 +1636,104 @@ impl ReturnStmt {
 }
 
 impl Node for ReturnStmt {
-    fn eval(self) -> Box<dyn Node> {
-        return Box::new(self);
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        let value = self.expr.eval(env)?;
+        return Err(EvalError::ControlFlow(ControlFlow::Return(value)));
     }
-    fn repr(&self) -> String {
-        return format!("return {}", &self.expr.repr());
+    fn format(&self, opts: &FormatOptions) -> String {
+        return format!("return {}", &self.expr.format(opts));
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        return tree_repr_of(depth, "ReturnStmt".to_string(), &[&*self.expr]);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        self.expr.check_types(checker);
+        return None;
     }
 }
+/// `mut name = expr` declares a binding that can be reassigned later, a
+/// plain `name = expr` binds immutably unless `name` is already mutable
+#[derive(Debug, Clone, PartialEq)]
 pub struct AssignmentStmt {
     identifier: Identifier,
+    // Type annotations are only kept for repr for now
+    annotation: Option<String>,
     expr: Box<dyn Node>,
+    mutable: bool,
 }
 
 impl AssignmentStmt {
-    pub fn new(identifier: Identifier, expression: Box<dyn Node>) -> Self {
+    pub fn new(
+        identifier: Identifier,
+        annotation: Option<String>,
+        expression: Box<dyn Node>,
+        mutable: bool,
+    ) -> Self {
         return AssignmentStmt {
-            identifier: identifier,
+            identifier,
+            annotation,
             expr: expression,
+            mutable,
         };
     }
+    /// Everything left of the `=`, as `mut name: type`
+    fn target(&self) -> String {
+        let mut target = self.identifier.literal.clone();
+        if self.mutable {
+            target = format!("{} {target}", Token::Mut);
+        }
+        if let Some(annotation) = &self.annotation {
+            target = format!("{target}: {annotation}");
+        }
+        return target;
+    }
 }
 
 impl Node for AssignmentStmt {
-    fn eval(self) -> Box<dyn Node> {
-        return Box::new(self);
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        let value = self.expr.eval(env)?;
+        let name = self.identifier.literal.clone();
+        if self.mutable {
+            env.declare(name, value, true);
+        } else {
+            env.assign(name, value)?;
+        }
+        return Ok(Value::Unit);
     }
-    fn repr(&self) -> String {
-        let expr_repr = &self.expr.repr();
-        return format!("{} = {}", self.identifier.repr(), expr_repr);
+    fn format(&self, opts: &FormatOptions) -> String {
+        let expr = self.expr.format(opts);
+        let op = opts.op(&Token::Assignment);
+        return format!("{}{op}{expr}", self.target());
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        let label = format!("AssignmentStmt({})", self.target());
+        return tree_repr_of(depth, label, &[&*self.expr]);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        let found = self.expr.check_types(checker);
+        let annotation =
+            self.annotation.as_deref().and_then(Type::from_annotation);
+        // An int is fine wherever a float is expected
+        if let (Some(annotation), Some(found)) = (annotation, found) {
+            if annotation != found
+                && (annotation, found) != (Type::Float, Type::Int)
+            {
+                checker.error(TypeError::AnnotationMismatch {
+                    name: self.identifier.literal.clone(),
+                    annotation,
+                    found,
+                });
+            }
+        }
+        checker.set(self.identifier.literal.clone(), annotation.or(found));
+        return None;
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Statement {}
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct BlockStmt {
     indent: usize,
     statements: Vec<Box<dyn Node>>,
@@ -227,61 +1745,677 @@ impl BlockStmt {
 }
 
 impl Node for BlockStmt {
-    fn eval(self) -> Box<dyn Node> {
-        return Box::new(self);
+    /// Evaluates each statement in order, producing the last one's result
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        let mut result = Value::Unit;
+        for stmt in &self.statements {
+            result = stmt.eval(env)?;
+        }
+        return Ok(result);
     }
-    fn repr(&self) -> String {
-        let spaces = "    ".repeat(self.indent);
+    fn format(&self, opts: &FormatOptions) -> String {
+        let spaces = opts.indent(self.indent);
         return self
             .statements
             .iter()
-            .map(|e| format!("{}{}", spaces, e.repr()))
+            .map(|e| format!("{}{}", spaces, e.format(opts)))
             .collect::<Vec<String>>()
             .join("\n");
     }
+    fn tree_repr(&self, depth: usize) -> String {
+        let stmts: Vec<&dyn Node> =
+            self.statements.iter().map(|e| &**e).collect();
+        let label = format!("BlockStmt({})", self.indent);
+        return tree_repr_of(depth, label, &stmts);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        for stmt in &self.statements {
+            stmt.check_types(checker);
+        }
+        return None;
+    }
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct FnArg {
     name: Identifier,
-    // default: Literal
+    annotation: Option<String>,
+    // Evaluated on each call that leaves the argument out
+    default: Option<Box<dyn Node>>,
+}
+
+impl FnArg {
+    pub fn new(
+        name: Identifier,
+        annotation: Option<String>,
+        default: Option<Box<dyn Node>>,
+    ) -> Self {
+        return FnArg {
+            name,
+            annotation,
+            default,
+        };
+    }
+    pub fn has_default(&self) -> bool {
+        return self.default.is_some();
+    }
+    /// `name: type = default`, as it appears between the parens
+    fn format(&self, opts: &FormatOptions) -> String {
+        let mut arg = self.name.literal.clone();
+        if let Some(annotation) = &self.annotation {
+            arg = format!("{arg}: {annotation}");
+        }
+        if let Some(default) = &self.default {
+            let op = opts.op(&Token::Assignment);
+            arg = format!("{arg}{op}{}", default.format(opts));
+        }
+        return arg;
+    }
 }
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct FnLiteral {
     name: Identifier,
-    args: Vec<Identifier>,
+    args: Vec<FnArg>,
+    return_type: Option<String>,
     definition: Box<BlockStmt>,
 }
 
 impl FnLiteral {
     pub fn new(
         name: Identifier,
-        args: Vec<Identifier>,
+        args: Vec<FnArg>,
+        return_type: Option<String>,
         definition: Box<BlockStmt>,
     ) -> Self {
         return FnLiteral {
             name,
             args,
+            return_type,
             definition,
         };
     }
+    pub fn name(&self) -> &str {
+        return &self.name.literal;
+    }
+    /// `name(args) -> type`, everything between `def` and the colon
+    fn signature(&self, opts: &FormatOptions) -> String {
+        let args = self
+            .args
+            .iter()
+            .map(|e| e.format(opts))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let mut signature = format!("{}({args})", self.name.literal);
+        if let Some(return_type) = &self.return_type {
+            signature = format!("{signature} {} {return_type}", Token::Arrow);
+        }
+        return signature;
+    }
 }
 
 impl Node for FnLiteral {
-    fn eval(self) -> Box<dyn Node> {
-        return Box::new(self);
+    /// Binds the function to its name
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        let function = Value::Fn(Rc::new(self.clone()));
+        env.declare(self.name.literal.clone(), function, false);
+        return Ok(Value::Unit);
     }
-    fn repr(&self) -> String {
-        let args: String = self
+    fn format(&self, opts: &FormatOptions) -> String {
+        return format!(
+            "{} {}:\n{}",
+            Token::Def,
+            self.signature(opts),
+            self.definition.format(opts)
+        );
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        let signature = self.signature(&FormatOptions::default());
+        let label = format!("FnLiteral({signature})");
+        return tree_repr_of(depth, label, &[&*self.definition]);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        checker.scoped(|checker| {
+            for arg in &self.args {
+                let default =
+                    arg.default.as_ref().and_then(|e| e.check_types(checker));
+                let ty =
+                    arg.annotation.as_deref().and_then(Type::from_annotation);
+                checker.set(arg.name.literal.clone(), ty.or(default));
+            }
+            self.definition.check_types(checker);
+        });
+        return None;
+    }
+}
+
+/// `struct Point: x, y`, a record type whose fields can be annotated like
+/// a function's parameters. Calling it makes an Instance
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructDef {
+    name: Identifier,
+    fields: Vec<FnArg>,
+}
+
+impl StructDef {
+    pub fn new(name: Identifier, fields: Vec<FnArg>) -> Self {
+        return StructDef { name, fields };
+    }
+    pub fn name(&self) -> &str {
+        return &self.name.literal;
+    }
+    /// `Point: x: int, y`, everything after the keyword
+    fn signature(&self, opts: &FormatOptions) -> String {
+        let fields: Vec<String> =
+            self.fields.iter().map(|field| field.format(opts)).collect();
+        return format!("{}: {}", self.name.literal, fields.join(", "));
+    }
+}
+
+impl Node for StructDef {
+    /// Binds the struct to its name
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        let def = Value::Struct(Rc::new(self.clone()));
+        env.declare(self.name.literal.clone(), def, false);
+        return Ok(Value::Unit);
+    }
+    fn format(&self, opts: &FormatOptions) -> String {
+        return format!("{} {}", Token::Struct, self.signature(opts));
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        let signature = self.signature(&FormatOptions::default());
+        return tree_repr_of(depth, format!("StructDef({signature})"), &[]);
+    }
+    fn check_types(&self, _checker: &mut TypeChecker) -> Option<Type> {
+        return None;
+    }
+}
+
+/// A value made by calling a struct, holding its fields in the order the
+/// struct declares them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instance {
+    pub def: Rc<StructDef>,
+    pub fields: Vec<Value>,
+}
+
+impl Instance {
+    pub fn get(&self, field: &str) -> Option<&Value> {
+        let ix = self
+            .def
+            .fields
+            .iter()
+            .position(|arg| arg.name.literal == field)?;
+        return self.fields.get(ix);
+    }
+    /// Each field's name alongside its value
+    pub fn named_fields(&self) -> impl Iterator<Item = (&str, &Value)> {
+        let names =
+            self.def.fields.iter().map(|arg| arg.name.literal.as_str());
+        return names.zip(&self.fields);
+    }
+}
+
+/// `target.field`, reading a field of a struct instance
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldAccess {
+    target: Box<dyn Node>,
+    field: Identifier,
+    // Where the dot is
+    span: NodeSpan,
+}
+
+impl FieldAccess {
+    pub fn new(target: Box<dyn Node>, field: Identifier, span: Span) -> Self {
+        return FieldAccess {
+            target,
+            field,
+            span: NodeSpan(span),
+        };
+    }
+}
+
+impl Node for FieldAccess {
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        let target = self.target.eval(env)?;
+        let field = &self.field.literal;
+        if let Value::Instance(instance) = &target {
+            if let Some(value) = instance.get(field) {
+                return Ok(value.clone());
+            }
+        }
+        let target = match target {
+            Value::Instance(instance) => instance.def.name().to_string(),
+            other => other.type_name().to_string(),
+        };
+        return Err(EvalError::NoField {
+            target,
+            field: field.clone(),
+        });
+    }
+    fn format(&self, opts: &FormatOptions) -> String {
+        let target = self.target.format(opts);
+        return format!("{target}{}{}", Token::Dot, self.field.literal);
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        let label = format!("FieldAccess({})", self.field.literal);
+        return tree_repr_of(depth, label, &[&*self.target]);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        self.target.check_types(checker);
+        return None;
+    }
+    fn span(&self) -> Option<Span> {
+        return Some(self.span.0);
+    }
+}
+
+/// `(x, y) -> x + y`, a function without a name whose body is a single
+/// expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct LambdaNode {
+    args: Vec<FnArg>,
+    body: Box<dyn Node>,
+}
+
+impl LambdaNode {
+    pub fn new(args: Vec<FnArg>, body: Box<dyn Node>) -> Self {
+        return LambdaNode { args, body };
+    }
+    /// Takes the parenthesised expressions in front of a `->` as the
+    /// lambda's parameters, which have to be plain names
+    pub fn from_params(
+        params: Vec<Box<dyn Node>>,
+        body: Box<dyn Node>,
+    ) -> Result<Self, ParseError> {
+        let mut args = Vec::new();
+        for param in params {
+            match downcast::<Identifier>(param) {
+                Ok(name) => args.push(FnArg::new(*name, None, None)),
+                Err(param) => {
+                    return Err(ParseError::InvalidTokenOrder(format!(
+                        "Expected Parameter Name - Found {}",
+                        param.repr()
+                    )));
+                }
+            }
+        }
+        return Ok(LambdaNode::new(args, body));
+    }
+    fn params(&self, opts: &FormatOptions) -> String {
+        return self
             .args
             .iter()
-            .map(|e| e.repr())
+            .map(|e| e.format(opts))
             .collect::<Vec<String>>()
             .join(", ");
+    }
+}
 
+impl Node for LambdaNode {
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        return Ok(Value::Lambda(Rc::new(Closure {
+            lambda: self.clone(),
+            captured: env.capture(),
+        })));
+    }
+    fn format(&self, opts: &FormatOptions) -> String {
         return format!(
-            "def {}({}):\n{}",
-            self.name.literal,
-            args,
-            self.definition.repr()
+            "({}) {} {}",
+            self.params(opts),
+            Token::Arrow,
+            self.body.format(opts)
+        );
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        let params = self.params(&FormatOptions::default());
+        let label = format!("LambdaNode({params})");
+        return tree_repr_of(depth, label, &[&*self.body]);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        checker.scoped(|checker| {
+            for arg in &self.args {
+                checker.set(arg.name.literal.clone(), None);
+            }
+            self.body.check_types(checker);
+        });
+        return None;
+    }
+}
+
+/// A lambda along with the bindings of the call it was made in, which it
+/// keeps seeing after that call returns
+#[derive(Debug, Clone, PartialEq)]
+pub struct Closure {
+    lambda: LambdaNode,
+    captured: HashMap<String, Value>,
+}
+
+/// `a, b = 1, 2`, with a value for every name, or `a, b = t` for a tuple or
+/// list `t` holding one
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnpackAssignStmt {
+    targets: Vec<Identifier>,
+    exprs: Vec<Box<dyn Node>>,
+}
+
+impl UnpackAssignStmt {
+    pub fn new(targets: Vec<Identifier>, exprs: Vec<Box<dyn Node>>) -> Self {
+        return UnpackAssignStmt { targets, exprs };
+    }
+    fn targets(&self) -> String {
+        return self
+            .targets
+            .iter()
+            .map(|t| t.literal.clone())
+            .collect::<Vec<String>>()
+            .join(", ");
+    }
+}
+
+impl Node for UnpackAssignStmt {
+    /// Every value is evaluated before any name is bound, so `a, b = b, a`
+    /// swaps
+    fn eval(&self, env: &mut Environment) -> Result<Value, EvalError> {
+        let mut values = self
+            .exprs
+            .iter()
+            .map(|e| e.eval(env))
+            .collect::<Result<Vec<Value>, EvalError>>()?;
+        // A single value is unpacked itself, and only now is its length
+        // known
+        if values.len() == 1 && self.targets.len() > 1 {
+            values = match values.remove(0) {
+                Value::Tuple(values) | Value::List(values) => values,
+                value => {
+                    return Err(EvalError::InvalidOperand(format!(
+                        "Expected Tuple Or List To Unpack - Found {}",
+                        value.repr()
+                    )));
+                }
+            };
+        }
+        if values.len() != self.targets.len() {
+            return Err(EvalError::InvalidOperand(format!(
+                "Expected {} Values To Unpack - Found {}",
+                self.targets.len(),
+                values.len()
+            )));
+        }
+        for (target, value) in self.targets.iter().zip(values) {
+            env.assign(target.literal.clone(), value)?;
+        }
+        return Ok(Value::Unit);
+    }
+    fn format(&self, opts: &FormatOptions) -> String {
+        let exprs = self
+            .exprs
+            .iter()
+            .map(|e| e.format(opts))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let op = opts.op(&Token::Assignment);
+        return format!("{}{op}{exprs}", self.targets());
+    }
+    fn tree_repr(&self, depth: usize) -> String {
+        let exprs: Vec<&dyn Node> = self.exprs.iter().map(|e| &**e).collect();
+        let label = format!("UnpackAssignStmt({})", self.targets());
+        return tree_repr_of(depth, label, &exprs);
+    }
+    fn check_types(&self, checker: &mut TypeChecker) -> Option<Type> {
+        let found: Vec<Option<Type>> =
+            self.exprs.iter().map(|e| e.check_types(checker)).collect();
+        // What a single value unpacks into isn't known, so those names
+        // become unknown
+        let mut found = found.into_iter();
+        for target in &self.targets {
+            checker.set(target.literal.clone(), found.next().flatten());
+        }
+        return None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Parser, Precedence};
+    use crate::runner::{run_source, RunError};
+    use crate::testing::run;
+    use crate::token::Tokenizer;
+
+    fn parse(src: &str) -> BlockStmt {
+        let tokens = Tokenizer::new(src.to_string()).tokenize_spanned();
+        return Parser::new(tokens.expect("test source lexes"))
+            .parse_program()
+            .expect("test source parses");
+    }
+
+    fn int(literal: &str) -> Box<dyn Node> {
+        let node = IntegerNode::new(Token::Int(literal.to_string()));
+        return Box::new(node.unwrap());
+    }
+
+    fn binary(op: Token, l: Box<dyn Node>, r: Box<dyn Node>) -> Box<dyn Node> {
+        return Box::new(BinaryExpr::new(op, l, r, Span::default()));
+    }
+
+    #[test]
+    fn evaluates_integer_arithmetic() {
+        let product = binary(Token::Mul, int("3"), int("4"));
+        let sum = binary(Token::Add, int("2"), product);
+        let value = sum.eval(&mut Environment::new()).unwrap();
+        assert_eq!(value, Value::Int(14));
+        let past_i32 = binary(Token::Add, int("9999999999"), int("1"));
+        let value = past_i32.eval(&mut Environment::new()).unwrap();
+        assert_eq!(value, Value::Int(10000000000));
+    }
+
+    #[test]
+    fn integer_literal_errors_say_why() {
+        let message = |literal: &str| {
+            let node = IntegerNode::new(Token::Int(literal.to_string()));
+            let Err(ParseError::InvalidTypeData(message)) = node else {
+                panic!("{literal} should not make an IntegerNode");
+            };
+            return message;
+        };
+        assert_eq!(
+            message("99999999999999999999"),
+            "integer literal 99999999999999999999 too large for i64"
+        );
+        assert_eq!(message("12ab"), "integer literal 12ab is not a number");
+    }
+
+    #[test]
+    fn conditional_repr_is_lowercase() {
+        let program = parse("if x:\n    y\nelse:\n    z");
+        assert_eq!(program.repr(), "if x:\n    y\nelse:\n    z");
+        for keyword in [Token::If, Token::Elif, Token::Else, Token::Def] {
+            let spelled = keyword.to_string();
+            assert_eq!(spelled, spelled.to_lowercase());
+        }
+    }
+
+    #[test]
+    fn ints_promote_to_floats() {
+        let float =
+            Box::new(FloatNode::new(Token::Float("2.5".into())).unwrap());
+        let sum = binary(Token::Add, int("1"), float)
+            .eval(&mut Environment::new())
+            .unwrap();
+        assert_eq!(sum, Value::Float(3.5));
+    }
+
+    #[test]
+    fn tree_repr_shows_nesting() {
+        assert_eq!(
+            parse("2 + 3 * 4").tree_repr(0),
+            "BlockStmt(0)\n    BinaryExpr(+)\n        IntegerNode(2)\n        \
+             BinaryExpr(*)\n            IntegerNode(3)\n            \
+             IntegerNode(4)"
+        );
+    }
+
+    #[test]
+    fn else_lines_up_with_its_if() {
+        let top = "if a:\n    b\nelse:\n    c";
+        assert_eq!(parse(top).repr(), top);
+        let nested =
+            "def f():\n    while a:\n        if b:\n            c\n        \
+                      else:\n            d\n    return 1";
+        assert_eq!(parse(nested).repr(), nested);
+    }
+
+    fn expr(src: &str) -> Box<dyn Node> {
+        let tokens = Tokenizer::new(src.to_string()).tokenize_spanned();
+        // Past the Indent every source starts with
+        let tokens = tokens.expect("test source lexes")[1..].to_vec();
+        return Parser::new(tokens)
+            .parse_expr(Precedence::Lowest)
+            .expect("test source parses");
+    }
+
+    #[test]
+    fn constant_folding() {
+        assert_eq!(fold_constants(expr("2 + 3 * 4")).repr(), "14");
+        assert_eq!(fold_constants(expr("-(2 ** 3) - 1")).repr(), "-9");
+        assert_eq!(fold_constants(expr("x + 3 * 4")).repr(), "(x + 12)");
+        assert_eq!(fold_constants(expr("1 / 0")).repr(), "(1 / 0)");
+    }
+
+    #[test]
+    fn trees_clone_and_debug_print() {
+        let tree = binary(
+            Token::Add,
+            int("1"),
+            binary(Token::Mul, int("2"), int("3")),
+        );
+        let cloned = tree.clone();
+        assert_eq!(cloned, tree);
+        assert_eq!(format!("{cloned:?}"), format!("{tree:?}"));
+        assert!(format!("{cloned:?}").starts_with("BinaryExpr { op: Add"));
+    }
+
+    #[test]
+    fn equal_reprs_can_be_unequal_trees() {
+        let sum = binary(Token::Add, int("1"), int("2"));
+        let name: Box<dyn Node> =
+            Box::new(Identifier::new("(1 + 2)".to_string()));
+        assert_eq!(sum.repr(), name.repr());
+        assert_ne!(sum, name);
+        assert_ne!(sum, binary(Token::Add, int("2"), int("1")));
+    }
+
+    #[test]
+    fn binary_expr_reports_its_operator_span() {
+        let program = parse("x\n1 *  (2 + 3)");
+        let span = program.statements[1].span().unwrap();
+        assert_eq!(
+            span,
+            Span {
+                byte: 4,
+                line: 2,
+                col: 3
+            }
         );
     }
+
+    #[test]
+    fn spans_are_left_out_of_equality() {
+        assert_eq!(parse("x = 2+3"), parse("x = 2 + 3"));
+        let spaced = "x = f(a[1], b[1:2], -c, d and e, 1 if g else 2, \
+                      h.i, j |> k)";
+        let packed = "x = f(a [1],b [1 : 2],- c,d  and  e,1  if g  else 2,\
+                      h .i,j|>k)";
+        assert_eq!(parse(spaced), parse(packed));
+        assert_ne!(parse("x = 2+3"), parse("x = 2-3"));
+    }
+
+    /// The value of the last statement of `src`
+    fn value(src: &str) -> Value {
+        return run_source(src, &mut Environment::new())
+            .expect("test source runs");
+    }
+
+    #[test]
+    fn user_defined_functions() {
+        let src = "def add(a, b):\n    return a + b\nadd(2, 3)";
+        assert_eq!(value(src), Value::Int(5));
+        let src = "def fact(n):\n    if n <= 1:\n        return 1\n    \
+                   return n * fact(n - 1)\nfact(10)";
+        assert_eq!(value(src), Value::Int(3628800));
+    }
+
+    #[test]
+    fn default_arguments() {
+        let inc = "def inc(x, by=1):\n    return x + by\n";
+        let run = |call: &str| {
+            return run_source(
+                &format!("{inc}{call}"),
+                &mut Environment::new(),
+            );
+        };
+        assert_eq!(run("inc(1)").unwrap(), Value::Int(2));
+        assert_eq!(run("inc(1, 10)").unwrap(), Value::Int(11));
+        // x has no default, so it can't be left out
+        assert!(matches!(
+            run("inc()"),
+            Err(RunError::Eval(EvalError::InvalidArgument {
+                name,
+                arg,
+                reason: ArgumentError::Missing,
+            })) if name == "inc" && arg == "x"
+        ));
+    }
+
+    #[test]
+    fn named_arguments() {
+        let sub = "def sub(a, b):\n    return a - b\n";
+        let run = |call: &str| {
+            return run_source(
+                &format!("{sub}{call}"),
+                &mut Environment::new(),
+            );
+        };
+        assert_eq!(run("sub(b=1, a=5)").unwrap(), Value::Int(4));
+        assert_eq!(run("sub(5, b=2)").unwrap(), Value::Int(3));
+        assert!(matches!(
+            run("sub(1, a=2)"),
+            Err(RunError::Eval(EvalError::InvalidArgument { .. }))
+        ));
+    }
+
+    #[test]
+    fn lambdas_and_closures() {
+        assert_eq!(value("f = (x) -> x + 1\nf(2)"), Value::Int(3));
+        let src = "def adder(n):\n    return (x) -> x + n\nadd2 = adder(2)\n\
+                   add2(5)";
+        assert_eq!(value(src), Value::Int(7));
+    }
+
+    #[test]
+    fn unpacks_values() {
+        let src = "a, b = 1, 2\nprint(a)\nprint(b)\n";
+        assert_eq!(run(src).unwrap(), "1\n2\n");
+    }
+
+    #[test]
+    fn unpacks_a_tuple_or_list_value() {
+        let src = "t = (1, 2)\na, b = t\nprint(a)\nprint(b)\n";
+        assert_eq!(run(src).unwrap(), "1\n2\n");
+        let src = "l = [3, 4]\na, b = l\nprint(a + b)\n";
+        assert_eq!(run(src).unwrap(), "7\n");
+    }
+
+    #[test]
+    fn unpacking_the_wrong_number_of_values_fails() {
+        assert!(matches!(run("a, b = 1, 2, 3"), Err(RunError::Parse(_))));
+        for src in ["t = (1, 2, 3)\na, b = t", "a, b = [1]", "a, b = 1"] {
+            let err = run(src).unwrap_err();
+            assert!(
+                matches!(err, RunError::Eval(EvalError::InvalidOperand(_))),
+                "{src}: {err}"
+            );
+        }
+    }
 }
@@ -0,0 +1,132 @@
+use crate::ast::call_value;
+use crate::eval::{Environment, EvalError, Value};
+
+/// Functions implemented in Rust that every program starts out with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    Print,
+    Map,
+    Filter,
+    Reduce,
+}
+
+impl Builtin {
+    pub const ALL: [Builtin; 4] = [
+        Builtin::Print,
+        Builtin::Map,
+        Builtin::Filter,
+        Builtin::Reduce,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Builtin::Print => "print",
+            Builtin::Map => "map",
+            Builtin::Filter => "filter",
+            Builtin::Reduce => "reduce",
+        };
+    }
+    /// The fewest and most arguments the builtin takes
+    fn arity(&self) -> (usize, usize) {
+        return match self {
+            Builtin::Print => (0, usize::MAX),
+            Builtin::Map | Builtin::Filter => (2, 2),
+            // The initial value is optional, as in Python
+            Builtin::Reduce => (2, 3),
+        };
+    }
+
+    /// Runs the builtin on arguments that have already been evaluated
+    pub fn call(
+        &self,
+        args: Vec<Value>,
+        env: &mut Environment,
+    ) -> Result<Value, EvalError> {
+        let (min, max) = self.arity();
+        if args.len() < min || args.len() > max {
+            return Err(EvalError::ArityMismatch {
+                name: self.name().to_string(),
+                expected: args.len().clamp(min, max),
+                found: args.len(),
+            });
+        }
+        if *self == Builtin::Print {
+            // Like Python, strings are written without their quotes
+            let line: Vec<String> =
+                args.iter().map(Value::to_string).collect();
+            writeln!(env.output(), "{}", line.join(" "))
+                .map_err(EvalError::Io)?;
+            return Ok(Value::Unit);
+        }
+        // The rest take the function to apply first and the list second
+        let mut args = args.into_iter();
+        let function = args.next().expect("arity was checked above");
+        let values = args.next().expect("arity was checked above");
+        let values = values.into_list()?;
+        match self {
+            Builtin::Map => {
+                let mapped = values
+                    .into_iter()
+                    .map(|value| call_value(&function, vec![value], env))
+                    .collect::<Result<Vec<Value>, EvalError>>()?;
+                return Ok(Value::List(mapped));
+            }
+            Builtin::Filter => {
+                let mut kept = Vec::new();
+                for value in values {
+                    let keep = vec![value.clone()];
+                    if call_value(&function, keep, env)?.truthy()? {
+                        kept.push(value);
+                    }
+                }
+                return Ok(Value::List(kept));
+            }
+            Builtin::Print => unreachable!("print returned above"),
+            Builtin::Reduce => {
+                let mut values = values.into_iter();
+                let Some(mut acc) = args.next().or_else(|| values.next())
+                else {
+                    return Err(EvalError::InvalidOperand(format!(
+                        "{} of an empty list needs an initial value",
+                        self.name()
+                    )));
+                };
+                for value in values {
+                    acc = call_value(&function, vec![acc, value], env)?;
+                }
+                return Ok(acc);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Builtin;
+    use crate::eval::{Environment, Value};
+    use crate::testing::{run, SharedOutput};
+
+    #[test]
+    fn map_and_filter() {
+        let src = "print(map((x) -> x * 2, [1,2,3]))";
+        assert_eq!(run(src).unwrap(), "[2, 4, 6]\n");
+        let src = "print(filter((x) -> x % 2 == 0, [1, 2, 3, 4]))";
+        assert_eq!(run(src).unwrap(), "[2, 4]\n");
+        let src =
+            "def odd(x):\n    return x % 2\nprint(filter(odd, [1, 2, 3]))";
+        assert_eq!(run(src).unwrap(), "[1, 3]\n");
+    }
+
+    #[test]
+    fn print_writes_to_the_output() {
+        let output = SharedOutput::default();
+        let mut env = Environment::with_output(Box::new(output.clone()));
+        let args = vec![Value::Str("hi".to_string()), Value::Int(42)];
+        assert_eq!(Builtin::Print.call(args, &mut env).unwrap(), Value::Unit);
+        assert_eq!(output.contents(), "hi 42\n");
+        assert_eq!(
+            run("print()\nprint([\"a\"], 'b')").unwrap(),
+            "\n[\"a\"] b\n"
+        );
+    }
+}
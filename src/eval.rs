@@ -0,0 +1,1113 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::ast::{
+    format_tuple, quote_char, quote_str, Closure, FnLiteral, Instance,
+    StructDef,
+};
+use crate::builtins::Builtin;
+use crate::token::Token;
+
+#[derive(Debug)]
+pub enum EvalError {
+    DivisionByZero,
+    IntegerOverflow,
+    InvalidOperand(String),
+    InvalidOperator(String),
+    Undefined(String),
+    NotCallable(String),
+    ImmutableAssignment(String),
+    Unsupported(String),
+    LoopLimit(usize),
+    RecursionLimit(usize),
+    Io(io::Error),
+    // Not a real failure, unwinds to the closest loop or function call,
+    // which handles it
+    ControlFlow(ControlFlow),
+    IndexOutOfBounds {
+        index: i64,
+        len: usize,
+    },
+    MissingKey(String),
+    NoField {
+        target: String,
+        field: String,
+    },
+    ArityMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    InvalidArgument {
+        name: String,
+        arg: String,
+        reason: ArgumentError,
+    },
+    TypeMismatch {
+        op: String,
+        left: &'static str,
+        right: &'static str,
+    },
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Self::DivisionByZero => write!(f, "division by zero"),
+            Self::IntegerOverflow => write!(f, "integer overflow"),
+            Self::InvalidOperand(reason) => {
+                write!(f, "invalid operand: {reason}")
+            }
+            Self::InvalidOperator(op) => {
+                write!(f, "{op} is not supported on these operands")
+            }
+            Self::Undefined(name) => write!(f, "{name} is not defined"),
+            Self::NotCallable(name) => write!(f, "{name} is not a function"),
+            Self::ImmutableAssignment(name) => {
+                write!(f, "cannot assign twice to immutable {name}")
+            }
+            Self::Unsupported(node) => {
+                write!(f, "evaluating {node} is not supported yet")
+            }
+            Self::LoopLimit(limit) => {
+                write!(f, "loop ran more than {limit} times")
+            }
+            Self::RecursionLimit(limit) => {
+                write!(f, "calls nested more than {limit} deep")
+            }
+            Self::Io(e) => write!(f, "{e}"),
+            Self::ControlFlow(ControlFlow::Break) => {
+                write!(f, "{} outside a loop", Token::Break)
+            }
+            Self::ControlFlow(ControlFlow::Continue) => {
+                write!(f, "{} outside a loop", Token::Continue)
+            }
+            Self::ControlFlow(ControlFlow::Return(_)) => {
+                write!(f, "{} outside a function", Token::Return)
+            }
+            Self::IndexOutOfBounds { index, len } => write!(
+                f,
+                "index {index} is out of bounds for a list of length {len}"
+            ),
+            Self::MissingKey(key) => write!(f, "key {key} is not in the map"),
+            Self::NoField { target, field } => {
+                write!(f, "{target} has no field {field}")
+            }
+            Self::ArityMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{name} takes {expected} arguments but was given {found}"
+            ),
+            Self::InvalidArgument { name, arg, reason } => match reason {
+                ArgumentError::Unknown => {
+                    write!(f, "{name} has no parameter named {arg}")
+                }
+                ArgumentError::Repeated => {
+                    write!(f, "{name} was given {arg} more than once")
+                }
+                ArgumentError::Missing => {
+                    write!(f, "{name} is missing a value for {arg}")
+                }
+            },
+            Self::TypeMismatch { op, left, right } => {
+                write!(f, "{op} can't be applied to {left} and {right}")
+            }
+        };
+    }
+}
+
+impl std::error::Error for EvalError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        return match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        };
+    }
+}
+
+/// What is wrong with a call's argument for one of the parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgumentError {
+    Unknown,
+    Repeated,
+    Missing,
+}
+
+/// How a `break` or `continue` leaves the body of a loop, or a `return`
+/// the body of a function
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlFlow {
+    Break,
+    Continue,
+    Return(Value),
+}
+
+/// The result of evaluating a node
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+    Char(char),
+    List(Vec<Value>),
+    Tuple(Vec<Value>),
+    // Entries in the order their keys were first added, as in Python
+    Map(Vec<(Value, Value)>),
+    // Shared, since every lookup of a function's name clones its value
+    Fn(Rc<FnLiteral>),
+    Lambda(Rc<Closure>),
+    Builtin(Builtin),
+    Struct(Rc<StructDef>),
+    Instance(Rc<Instance>),
+    // The value of `none`, unlike Unit, which is what statements evaluate to
+    None,
+    Unit,
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        return match self {
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::Bool(_) => "bool",
+            Value::Str(_) => "str",
+            Value::Char(_) => "char",
+            Value::List(_) => "list",
+            Value::Tuple(_) => "tuple",
+            Value::Map(_) => "map",
+            Value::Fn(_) | Value::Lambda(_) | Value::Builtin(_) => "fn",
+            Value::Struct(_) => "struct",
+            Value::Instance(_) => "instance",
+            Value::None => "none",
+            Value::Unit => "unit",
+        };
+    }
+    /// Like Display, but quotes strings and chars the way they'd be written
+    /// in source
+    pub fn repr(&self) -> String {
+        return match self {
+            Value::Str(s) => quote_str(s),
+            Value::Char(ch) => quote_char(*ch),
+            _ => self.to_string(),
+        };
+    }
+    /// Whether the value counts as true where a condition is expected. As
+    /// in Python, zero, empty strings and lists, and none are false, while
+    /// anything else is true. Functions are an error rather than always
+    /// true, since testing one is most likely a missing call
+    pub fn truthy(&self) -> Result<bool, EvalError> {
+        return match self {
+            Value::Bool(b) => Ok(*b),
+            Value::Int(i) => Ok(*i != 0),
+            Value::Float(x) => Ok(*x != 0.0),
+            Value::Str(s) => Ok(!s.is_empty()),
+            Value::Char(_) | Value::Instance(_) => Ok(true),
+            Value::List(values) | Value::Tuple(values) => {
+                Ok(!values.is_empty())
+            }
+            Value::Map(entries) => Ok(!entries.is_empty()),
+            Value::None => Ok(false),
+            Value::Fn(_)
+            | Value::Lambda(_)
+            | Value::Builtin(_)
+            | Value::Struct(_)
+            | Value::Unit => Err(EvalError::InvalidOperand(format!(
+                "{} has no truth value",
+                self.repr()
+            ))),
+        };
+    }
+    pub fn into_list(self) -> Result<Vec<Value>, EvalError> {
+        return match self {
+            Value::List(values) => Ok(values),
+            _ => Err(self.expected("List")),
+        };
+    }
+    fn expected(&self, kind: &str) -> EvalError {
+        return EvalError::InvalidOperand(format!(
+            "Expected {kind} - Found {}",
+            self.repr()
+        ));
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Value::Int(i) => write!(f, "{i}"),
+            // Debug keeps the trailing `.0` so whole floats don't read as ints
+            Value::Float(x) => write!(f, "{x:?}"),
+            Value::Bool(b) => {
+                write!(f, "{}", if *b { Token::True } else { Token::False })
+            }
+            Value::Str(s) => write!(f, "{s}"),
+            Value::Char(ch) => write!(f, "{ch}"),
+            // Elements are shown as they'd be written, so strings in a list
+            // keep their quotes
+            Value::List(values) => {
+                let values: Vec<String> =
+                    values.iter().map(Value::repr).collect();
+                write!(f, "[{}]", values.join(", "))
+            }
+            Value::Tuple(values) => {
+                let values: Vec<String> =
+                    values.iter().map(Value::repr).collect();
+                write!(f, "{}", format_tuple(&values))
+            }
+            Value::Map(entries) => {
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(key, value)| {
+                        format!("{}: {}", key.repr(), value.repr())
+                    })
+                    .collect();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+            Value::Fn(function) => write!(f, "<fn {}>", function.name()),
+            Value::Lambda(_) => write!(f, "<lambda>"),
+            Value::Builtin(builtin) => {
+                write!(f, "<builtin {}>", builtin.name())
+            }
+            Value::Struct(def) => write!(f, "<struct {}>", def.name()),
+            // Written like a call with named arguments that would make it
+            Value::Instance(instance) => {
+                let fields: Vec<String> = instance
+                    .named_fields()
+                    .map(|(name, value)| format!("{name}={}", value.repr()))
+                    .collect();
+                write!(f, "{}({})", instance.def.name(), fields.join(", "))
+            }
+            Value::None => write!(f, "{}", Token::None),
+            Value::Unit => write!(f, "()"),
+        };
+    }
+}
+
+/// The most bytes a string repetition may build
+const MAX_REPEAT_LEN: usize = 1 << 28;
+
+/// Applies a binary arithmetic or comparison operator to two evaluated
+/// operands
+pub fn eval_binary(
+    op: &Token,
+    l: Value,
+    r: Value,
+) -> Result<Value, EvalError> {
+    let invalid = || EvalError::InvalidOperator(op.to_string());
+    if let Token::Eq
+    | Token::NotEq
+    | Token::Lt
+    | Token::LtEq
+    | Token::Gt
+    | Token::GtEq = op
+    {
+        return eval_comparison(op, &l, &r);
+    }
+    match (l, r) {
+        (Value::Str(l), Value::Str(r)) if *op == Token::Add => {
+            return Ok(Value::Str(format!("{l}{r}")));
+        }
+        // Like Python, repeating a negative number of times gives ""
+        (Value::Str(s), Value::Int(n)) | (Value::Int(n), Value::Str(s))
+            if *op == Token::Mul =>
+        {
+            let times = usize::try_from(n.max(0)).unwrap_or(usize::MAX);
+            // Checked before anything is allocated, since the process
+            // aborts if that fails
+            let len = s.len().checked_mul(times);
+            if len.is_none_or(|len| len > MAX_REPEAT_LEN) {
+                return Err(EvalError::InvalidOperand(format!(
+                    "repeating a string of {} bytes {n} times is longer than \
+                     {MAX_REPEAT_LEN} bytes",
+                    s.len()
+                )));
+            }
+            return Ok(Value::Str(s.repeat(times)));
+        }
+        (Value::Int(l), Value::Int(r)) => {
+            let value = match op {
+                Token::Add => l.checked_add(r),
+                Token::Sub => l.checked_sub(r),
+                Token::Mul => l.checked_mul(r),
+                Token::Div if r == 0 => {
+                    return Err(EvalError::DivisionByZero);
+                }
+                Token::Div => l.checked_div(r),
+                Token::Mod if r == 0 => {
+                    return Err(EvalError::DivisionByZero);
+                }
+                // Like Python the remainder takes the sign of the divisor
+                Token::Mod => l.checked_rem(r).map(|m| {
+                    if m != 0 && (m < 0) != (r < 0) {
+                        m + r
+                    } else {
+                        m
+                    }
+                }),
+                // A negative exponent can't stay an integer
+                Token::Pow if r < 0 => {
+                    return Ok(Value::Float((l as f64).powf(r as f64)));
+                }
+                Token::Pow => {
+                    u32::try_from(r).ok().and_then(|r| l.checked_pow(r))
+                }
+                _ => return Err(invalid()),
+            };
+            return value.map(Value::Int).ok_or(EvalError::IntegerOverflow);
+        }
+        // Any float operand makes the whole operation float
+        (
+            l @ (Value::Int(_) | Value::Float(_)),
+            r @ (Value::Int(_) | Value::Float(_)),
+        ) => {
+            let (l, r) = (as_float(&l)?, as_float(&r)?);
+            let value = match op {
+                Token::Add => l + r,
+                Token::Sub => l - r,
+                Token::Mul => l * r,
+                Token::Div | Token::Mod if r == 0.0 => {
+                    return Err(EvalError::DivisionByZero);
+                }
+                Token::Div => l / r,
+                Token::Mod => l - r * (l / r).floor(),
+                Token::Pow => l.powf(r),
+                _ => return Err(invalid()),
+            };
+            return Ok(Value::Float(value));
+        }
+        // Strings support nothing else, with each other or with numbers,
+        // and nothing else supports arithmetic at all
+        (l, r) => {
+            return Err(EvalError::TypeMismatch {
+                op: op.to_string(),
+                left: l.type_name(),
+                right: r.type_name(),
+            });
+        }
+    }
+}
+
+/// Applies a comparison operator to two evaluated operands
+pub fn eval_comparison(
+    op: &Token,
+    l: &Value,
+    r: &Value,
+) -> Result<Value, EvalError> {
+    let ordering = match (l, r) {
+        (Value::Int(l), Value::Int(r)) => Some(l.cmp(r)),
+        (Value::Str(l), Value::Str(r)) => Some(l.cmp(r)),
+        (Value::Char(l), Value::Char(r)) => Some(l.cmp(r)),
+        // Booleans have no order, only equality
+        (Value::Bool(l), Value::Bool(r)) => match op {
+            Token::Eq | Token::NotEq => Some(l.cmp(r)),
+            _ => return Err(EvalError::InvalidOperator(op.to_string())),
+        },
+        // Anything can be checked for none, but none has no order
+        (Value::None, _) | (_, Value::None) => match op {
+            Token::Eq | Token::NotEq => {
+                let both = matches!((l, r), (Value::None, Value::None));
+                both.then_some(Ordering::Equal)
+            }
+            _ => return Err(EvalError::InvalidOperator(op.to_string())),
+        },
+        (Value::Int(_) | Value::Float(_), Value::Int(_) | Value::Float(_)) => {
+            // NaN is unordered, so compares false to everything
+            as_float(l)?.partial_cmp(&as_float(r)?)
+        }
+        // Collections are equal when their elements are, but have no order
+        (Value::List(l), Value::List(r))
+        | (Value::Tuple(l), Value::Tuple(r)) => match op {
+            Token::Eq | Token::NotEq => {
+                elements_equal(l, r)?.then_some(Ordering::Equal)
+            }
+            _ => return Err(EvalError::InvalidOperator(op.to_string())),
+        },
+        (Value::Map(l), Value::Map(r)) => match op {
+            Token::Eq | Token::NotEq => {
+                entries_equal(l, r)?.then_some(Ordering::Equal)
+            }
+            _ => return Err(EvalError::InvalidOperator(op.to_string())),
+        },
+        _ => {
+            return Err(EvalError::TypeMismatch {
+                op: op.to_string(),
+                left: l.type_name(),
+                right: r.type_name(),
+            })
+        }
+    };
+    let result = match op {
+        Token::Eq => ordering == Some(Ordering::Equal),
+        Token::NotEq => ordering != Some(Ordering::Equal),
+        Token::Lt => ordering == Some(Ordering::Less),
+        Token::LtEq => {
+            matches!(ordering, Some(Ordering::Less | Ordering::Equal))
+        }
+        Token::Gt => ordering == Some(Ordering::Greater),
+        _ => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+    };
+    return Ok(Value::Bool(result));
+}
+
+/// Whether `l == r`, with the rules that apply at the top level also
+/// applying inside lists, tuples and maps, so `[1] == [1.0]`
+fn values_equal(l: &Value, r: &Value) -> Result<bool, EvalError> {
+    return Ok(eval_comparison(&Token::Eq, l, r)? == Value::Bool(true));
+}
+
+/// Whether two lists or tuples hold equal elements in the same order
+fn elements_equal(l: &[Value], r: &[Value]) -> Result<bool, EvalError> {
+    if l.len() != r.len() {
+        return Ok(false);
+    }
+    for (l, r) in l.iter().zip(r) {
+        if !values_equal(l, r)? {
+            return Ok(false);
+        }
+    }
+    return Ok(true);
+}
+
+/// Whether two maps hold equal values for the same keys, whatever order
+/// the keys were added in. Keys are matched exactly, as lookups match them
+fn entries_equal(
+    l: &[(Value, Value)],
+    r: &[(Value, Value)],
+) -> Result<bool, EvalError> {
+    if l.len() != r.len() {
+        return Ok(false);
+    }
+    for (key, l) in l {
+        let Some((_, r)) = r.iter().find(|(other, _)| other == key) else {
+            return Ok(false);
+        };
+        if !values_equal(l, r)? {
+            return Ok(false);
+        }
+    }
+    return Ok(true);
+}
+
+/// Reads a float or an integer, widening the integer, for arithmetic on
+/// mixed operands
+fn as_float(value: &Value) -> Result<f64, EvalError> {
+    return match value {
+        Value::Float(x) => Ok(*x),
+        Value::Int(i) => Ok(*i as f64),
+        _ => Err(value.expected("Number")),
+    };
+}
+
+/// Applies a prefix operator to its evaluated operand
+pub fn eval_unary(op: &Token, operand: Value) -> Result<Value, EvalError> {
+    return match (op, operand) {
+        (Token::Sub, Value::Int(i)) => i
+            .checked_neg()
+            .map(Value::Int)
+            .ok_or(EvalError::IntegerOverflow),
+        (Token::Sub, Value::Float(x)) => Ok(Value::Float(-x)),
+        (Token::Sub, operand) => Err(operand.expected("Number")),
+        (Token::Bang | Token::Not, operand) => {
+            Ok(Value::Bool(!operand.truthy()?))
+        }
+        _ => Err(EvalError::InvalidOperator(op.to_string())),
+    };
+}
+
+/// Looks up `target[index]`, where a negative index counts back from the
+/// end as in Python
+pub fn eval_index(target: Value, index: Value) -> Result<Value, EvalError> {
+    if let Value::Map(entries) = target {
+        return entries
+            .into_iter()
+            .find(|(key, _)| *key == index)
+            .map(|(_, value)| value)
+            .ok_or_else(|| EvalError::MissingKey(index.repr()));
+    }
+    let mut values = match target {
+        Value::Tuple(values) => values,
+        target => target.into_list()?,
+    };
+    let Value::Int(i) = index else {
+        return Err(index.expected("Integer"));
+    };
+    let len = values.len();
+    let position = if i < 0 {
+        len.checked_sub(i.unsigned_abs() as usize)
+    } else {
+        Some(i as usize).filter(|&i| i < len)
+    };
+    let Some(position) = position else {
+        return Err(EvalError::IndexOutOfBounds { index: i, len });
+    };
+    return Ok(values.swap_remove(position));
+}
+
+/// Takes every `step`th element from `start` up to but not including
+/// `stop` of a list, tuple or string, giving the same kind back. As in
+/// Python, negative bounds count back from the end, bounds past either end
+/// are clamped, and a negative step walks backwards
+pub fn eval_slice(
+    target: Value,
+    start: Option<i64>,
+    stop: Option<i64>,
+    step: Option<i64>,
+) -> Result<Value, EvalError> {
+    let step = step.unwrap_or(1);
+    if step == 0 {
+        return Err(EvalError::InvalidOperand(
+            "slice step cannot be zero".to_string(),
+        ));
+    }
+    let positions = |len| slice_positions(len, start, stop, step);
+    return match target {
+        Value::List(values) => Ok(Value::List(
+            positions(values.len()).map(|i| values[i].clone()).collect(),
+        )),
+        Value::Tuple(values) => Ok(Value::Tuple(
+            positions(values.len()).map(|i| values[i].clone()).collect(),
+        )),
+        Value::Str(s) => {
+            let chars: Vec<char> = s.chars().collect();
+            Ok(Value::Str(positions(chars.len()).map(|i| chars[i]).collect()))
+        }
+        target => Err(target.expected("List")),
+    };
+}
+
+/// The positions a slice of something `len` long takes, in order
+fn slice_positions(
+    len: usize,
+    start: Option<i64>,
+    stop: Option<i64>,
+    step: i64,
+) -> impl Iterator<Item = usize> {
+    let len = len as i64;
+    // Walking backwards, -1 stands for the position before the first
+    let (first, last) = if step > 0 { (0, len) } else { (len - 1, -1) };
+    let clamp = |bound: i64| {
+        let bound = if bound < 0 { bound + len } else { bound };
+        return bound.clamp(first.min(last), first.max(last));
+    };
+    let mut i = start.map_or(first, clamp);
+    let stop = stop.map_or(last, clamp);
+    return std::iter::from_fn(move || {
+        if (step > 0 && i >= stop) || (step < 0 && i <= stop) {
+            return None;
+        }
+        let position = i as usize;
+        // A step too big to take means there is nothing after this one
+        i = i.checked_add(step).unwrap_or(stop);
+        return Some(position);
+    });
+}
+
+/// Adds `key` to a map's entries, replacing the value of an equal key
+/// already there but keeping its place. Keys are limited to values that
+/// compare reliably, so no floats, lists or functions
+pub fn insert_entry(
+    entries: &mut Vec<(Value, Value)>,
+    key: Value,
+    value: Value,
+) -> Result<(), EvalError> {
+    if !matches!(
+        key,
+        Value::Int(_)
+            | Value::Bool(_)
+            | Value::Str(_)
+            | Value::Char(_)
+            | Value::None
+    ) {
+        return Err(EvalError::InvalidOperand(format!(
+            "a {} can't be a map key",
+            key.type_name()
+        )));
+    }
+    match entries.iter_mut().find(|(existing, _)| *existing == key) {
+        Some((_, existing)) => *existing = value,
+        None => entries.push((key, value)),
+    }
+    return Ok(());
+}
+
+struct Binding {
+    value: Value,
+    mutable: bool,
+}
+
+/// How long and how deep a program may run, so runaway code fails rather
+/// than hanging or overflowing the stack. None leaves it unbounded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Most times a single loop may run its body
+    pub loops: Option<usize>,
+    /// Most function calls that may be in progress at once
+    pub recursion: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        return Limits {
+            loops: None,
+            // Well short of where a debug build runs out of stack
+            recursion: Some(256),
+        };
+    }
+}
+
+/// Variable bindings visible while evaluating a program
+pub struct Environment {
+    // The global scope, then one for each function call being evaluated
+    scopes: Vec<HashMap<String, Binding>>,
+    limits: Limits,
+    // Where print writes to
+    output: Box<dyn Write>,
+}
+
+impl Default for Environment {
+    /// An environment with only the builtins bound
+    fn default() -> Self {
+        let globals = Builtin::ALL.map(|builtin| {
+            let binding = Binding {
+                value: Value::Builtin(builtin),
+                mutable: false,
+            };
+            return (builtin.name().to_string(), binding);
+        });
+        return Environment {
+            scopes: vec![HashMap::from(globals)],
+            limits: Limits::default(),
+            output: Box::new(io::stdout()),
+        };
+    }
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        return Environment::default();
+    }
+    /// An environment whose loops fail with LoopLimit rather than run their
+    /// body more than `limit` times, so a runaway loop can't hang
+    pub fn with_loop_limit(limit: usize) -> Self {
+        let limits = Limits {
+            loops: Some(limit),
+            ..Limits::default()
+        };
+        return Environment::with_config(Box::new(io::stdout()), limits);
+    }
+    /// An environment whose prints go to `output` rather than stdout, so
+    /// they can be captured
+    pub fn with_output(output: Box<dyn Write>) -> Self {
+        return Environment::with_config(output, Limits::default());
+    }
+    pub fn with_config(output: Box<dyn Write>, limits: Limits) -> Self {
+        return Environment {
+            output,
+            limits,
+            ..Environment::default()
+        };
+    }
+    pub fn output(&mut self) -> &mut dyn Write {
+        return &mut *self.output;
+    }
+    /// Errors if a loop that has run `iterations` times is at the limit
+    pub fn check_loop_limit(
+        &self,
+        iterations: usize,
+    ) -> Result<(), EvalError> {
+        match self.limits.loops {
+            Some(limit) if iterations >= limit => {
+                return Err(EvalError::LoopLimit(limit));
+            }
+            _ => return Ok(()),
+        }
+    }
+    /// Errors if as many calls are in progress as the limit allows, before
+    /// another is made
+    pub fn check_recursion_limit(&self) -> Result<(), EvalError> {
+        // Every scope past the global one is a call
+        let depth = self.scopes.len() - 1;
+        match self.limits.recursion {
+            Some(limit) if depth >= limit => {
+                return Err(EvalError::RecursionLimit(limit));
+            }
+            _ => return Ok(()),
+        }
+    }
+    /// Runs `eval` in a new scope for a function call. It sees the globals
+    /// but not the caller's bindings, and whatever it binds is dropped
+    /// afterwards
+    pub fn scoped<T>(&mut self, eval: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes.push(HashMap::new());
+        let result = eval(self);
+        self.scopes.pop();
+        return result;
+    }
+    /// The bindings a lambda made here closes over. Globals are left out,
+    /// as they are still in scope wherever it's called
+    pub fn capture(&self) -> HashMap<String, Value> {
+        if self.scopes.len() == 1 {
+            return HashMap::new();
+        }
+        let local = self.scopes.last().expect("the global scope is kept");
+        return local
+            .iter()
+            .map(|(name, b)| (name.clone(), b.value.clone()))
+            .collect();
+    }
+    fn local(&mut self) -> &mut HashMap<String, Binding> {
+        return self.scopes.last_mut().expect("the global scope is kept");
+    }
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        let binding = match self.scopes.last()?.get(name) {
+            Some(binding) => Some(binding),
+            None => self.scopes[0].get(name),
+        };
+        return binding.map(|b| &b.value);
+    }
+    /// Binds `name` afresh in the current scope, replacing any binding it
+    /// already had there
+    pub fn declare(&mut self, name: String, value: Value, mutable: bool) {
+        self.local().insert(name, Binding { value, mutable });
+    }
+    /// Updates `name` if it is mutable, or binds it immutably if it is new
+    /// to the current scope
+    pub fn assign(
+        &mut self,
+        name: String,
+        value: Value,
+    ) -> Result<(), EvalError> {
+        match self.local().get_mut(&name) {
+            Some(binding) if binding.mutable => binding.value = value,
+            Some(_) => return Err(EvalError::ImmutableAssignment(name)),
+            None => self.declare(name, value, false),
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{run_source, RunError};
+    use crate::testing::run;
+
+    #[test]
+    fn bindings_outlive_the_statement_that_made_them() {
+        let mut env = Environment::new();
+        run_source("x = 5", &mut env).unwrap();
+        assert_eq!(env.get("x").map(|x| x.repr()).unwrap(), "5");
+        assert_eq!(run_source("x + 1", &mut env).unwrap().repr(), "6");
+        let undefined = run_source("y + 1", &mut Environment::new());
+        assert!(matches!(
+            undefined,
+            Err(RunError::Eval(EvalError::Undefined(_)))
+        ));
+    }
+
+    fn eval(src: &str) -> Value {
+        return run_source(src, &mut Environment::new())
+            .expect("test source runs");
+    }
+
+    #[test]
+    fn eval_gives_a_value_of_the_right_kind() {
+        assert_eq!(eval("2 + 3"), Value::Int(5));
+        assert_eq!(eval("2 < 3"), Value::Bool(true));
+        assert_eq!(eval("1.5 == 2"), Value::Bool(false));
+    }
+
+    fn str_value(s: &str) -> Value {
+        return Value::Str(s.to_string());
+    }
+
+    #[test]
+    fn equality_of_ints_and_strings() {
+        let cases = [
+            (Token::Eq, Value::Int(2), Value::Int(2), true),
+            (Token::NotEq, Value::Int(2), Value::Int(3), true),
+            (Token::Eq, str_value("a"), str_value("a"), true),
+            (Token::Eq, str_value("a"), str_value("b"), false),
+            (Token::NotEq, str_value("a"), str_value("b"), true),
+        ];
+        for (op, l, r, expected) in cases {
+            let compared = eval_binary(&op, l, r).unwrap();
+            assert_eq!(compared, Value::Bool(expected));
+        }
+        let err = eval_binary(&Token::Eq, Value::Int(1), str_value("1"));
+        assert!(matches!(
+            err,
+            Err(EvalError::TypeMismatch {
+                left: "int",
+                right: "str",
+                ..
+            })
+        ));
+    }
+
+    fn ints(values: &[i64]) -> Value {
+        return Value::List(values.iter().copied().map(Value::Int).collect());
+    }
+
+    #[test]
+    fn lists_tuples_and_maps_compare_by_their_elements() {
+        assert_eq!(eval("[1, [2, 3]] == [1, [2, 3]]"), Value::Bool(true));
+        assert_eq!(eval("[1, 2] == [1.0, 2.0]"), Value::Bool(true));
+        assert_eq!(eval("[1, 2] == [2, 1]"), Value::Bool(false));
+        assert_eq!(eval("[1, 2] != [1]"), Value::Bool(true));
+        assert_eq!(eval("(1, \"a\") == (1, \"a\")"), Value::Bool(true));
+        let maps = "{\"a\": 1, \"b\": [2]} == {\"b\": [2.0], \"a\": 1}";
+        assert_eq!(eval(maps), Value::Bool(true));
+        assert_eq!(eval("{\"a\": 1} == {\"a\": 2}"), Value::Bool(false));
+        assert_eq!(eval("{\"a\": 1} != {\"b\": 1}"), Value::Bool(true));
+        let ordered = eval_binary(&Token::Lt, ints(&[1]), ints(&[2]));
+        assert!(
+            matches!(ordered, Err(EvalError::InvalidOperator(_))),
+            "{ordered:?}"
+        );
+    }
+
+    #[test]
+    fn list_indexing() {
+        assert_eq!(
+            eval_index(ints(&[1, 2, 3]), Value::Int(1)).unwrap(),
+            Value::Int(2)
+        );
+        assert_eq!(
+            eval_index(ints(&[1, 2, 3]), Value::Int(-1)).unwrap(),
+            Value::Int(3)
+        );
+        assert!(matches!(
+            eval_index(ints(&[1, 2, 3]), Value::Int(3)),
+            Err(EvalError::IndexOutOfBounds { index: 3, len: 3 })
+        ));
+        assert!(matches!(
+            eval_index(ints(&[1, 2, 3]), Value::Int(-4)),
+            Err(EvalError::IndexOutOfBounds { index: -4, len: 3 })
+        ));
+        assert_eq!(eval("x = [1, 2, 3]\nx[0] + x[-1]"), Value::Int(4));
+    }
+
+    #[test]
+    fn each_failure_has_its_own_error() {
+        let failing = |src: &str| match run(src) {
+            Err(RunError::Eval(e)) => e,
+            other => panic!("{src} should fail to evaluate, not {other:?}"),
+        };
+        let e = failing("1 / 0");
+        assert!(matches!(e, EvalError::DivisionByZero), "{e}");
+        let e = failing("9223372036854775807 + 1");
+        assert!(matches!(e, EvalError::IntegerOverflow), "{e}");
+        let e = failing("true < false");
+        assert!(matches!(e, EvalError::InvalidOperator(_)), "{e}");
+        let e = failing("-\"a\"");
+        assert!(matches!(e, EvalError::InvalidOperand(_)), "{e}");
+        let e = failing("y + 1");
+        assert!(matches!(e, EvalError::Undefined(_)), "{e}");
+        let e = failing("x = 1\nx(2)");
+        assert!(matches!(e, EvalError::NotCallable(_)), "{e}");
+        let e = failing("{\"a\": 1}[\"b\"]");
+        assert!(matches!(e, EvalError::MissingKey(_)), "{e}");
+        let e = failing("def f(a):\n    return a\nf(1, 2)");
+        assert!(matches!(e, EvalError::ArityMismatch { .. }), "{e}");
+        let e = failing("1 + \"a\"");
+        assert!(matches!(e, EvalError::TypeMismatch { .. }), "{e}");
+        let e = failing("def f(n):\n    return f(n + 1)\nf(0)");
+        assert!(matches!(e, EvalError::RecursionLimit(_)), "{e}");
+        let e = failing("x = 1\nx = 2");
+        assert!(matches!(e, EvalError::ImmutableAssignment(_)), "{e}");
+        let e = failing("[1][5]");
+        assert!(matches!(e, EvalError::IndexOutOfBounds { .. }), "{e}");
+    }
+
+    #[test]
+    fn string_concatenation_and_unsupported_operators() {
+        let joined = eval_binary(&Token::Add, str_value("ab"), str_value("c"));
+        assert_eq!(joined.unwrap(), str_value("abc"));
+        let err = eval_binary(&Token::Sub, str_value("ab"), str_value("b"));
+        assert!(matches!(
+            err,
+            Err(EvalError::TypeMismatch {
+                left: "str",
+                right: "str",
+                ..
+            })
+        ));
+        let err = eval_binary(&Token::Sub, ints(&[1]), Value::Int(2));
+        assert!(matches!(
+            err,
+            Err(EvalError::TypeMismatch {
+                left: "list",
+                right: "int",
+                ..
+            })
+        ));
+        let err = eval_binary(&Token::Mul, Value::Bool(true), Value::Int(2));
+        assert!(matches!(
+            err,
+            Err(EvalError::TypeMismatch {
+                left: "bool",
+                right: "int",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn none_compares_but_doesnt_add() {
+        assert_eq!(eval("none"), Value::None);
+        assert_eq!(eval("none == none"), Value::Bool(true));
+        assert_eq!(eval("none == 0"), Value::Bool(false));
+        assert_eq!(
+            run("if none:\n    print(1)\nelse:\n    print(2)\n").unwrap(),
+            "2\n"
+        );
+        let added = run_source("none + 1", &mut Environment::new());
+        assert!(
+            matches!(
+                added,
+                Err(RunError::Eval(EvalError::TypeMismatch {
+                    left: "none",
+                    right: "int",
+                    ..
+                }))
+            ),
+            "{added:?}"
+        );
+    }
+
+    #[test]
+    fn conditions_go_by_truthiness() {
+        let branch = |cond: &str| {
+            let src = format!(
+                "if {cond}:\n    print(\"then\")\nelse:\n    print(\"else\")\n"
+            );
+            return run(&src).unwrap();
+        };
+        for falsy in ["0", "0.0", "\"\"", "[]", "none", "false"] {
+            assert_eq!(branch(falsy), "else\n", "{falsy}");
+        }
+        for truthy in ["1", "\"x\"", "[0]", "true"] {
+            assert_eq!(branch(truthy), "then\n", "{truthy}");
+        }
+        let src = "mut n = 3\nwhile n:\n    n -= 1\nprint(n)\n";
+        assert_eq!(run(src).unwrap(), "0\n");
+    }
+
+    #[test]
+    fn and_or_skip_their_right_side() {
+        let noisy = "def f():\n    print(\"f\")\n    return true\n";
+        let cases = [
+            ("false and f()", "false\n"),
+            ("true or f()", "true\n"),
+            ("true and f()", "f\ntrue\n"),
+            ("false or f()", "f\ntrue\n"),
+        ];
+        for (expr, printed) in cases {
+            let src = format!("{noisy}print({expr})\n");
+            assert_eq!(run(&src).unwrap(), printed, "{expr}");
+        }
+    }
+
+    #[test]
+    fn map_literals_and_lookup() {
+        assert_eq!(eval("m = {}\nm"), Value::Map(Vec::new()));
+        let entries = vec![
+            (Value::Str("a".to_string()), Value::Int(1)),
+            (Value::Str("b".to_string()), Value::Int(2)),
+        ];
+        assert_eq!(eval("m = {\"a\": 1, \"b\": 2}\nm"), Value::Map(entries));
+        assert_eq!(eval("m = {\"a\": 1, \"b\": 2}\nm[\"a\"]"), Value::Int(1));
+        let missing = run_source("m = {}\nm[\"a\"]", &mut Environment::new());
+        assert!(
+            matches!(missing, Err(RunError::Eval(EvalError::MissingKey(_)))),
+            "{missing:?}"
+        );
+    }
+
+    #[test]
+    fn parens_group_unless_there_is_a_comma() {
+        assert_eq!(eval("(1 + 2)"), Value::Int(3));
+        assert_eq!(eval("(1)"), Value::Int(1));
+        let pair = Value::Tuple(vec![Value::Int(1), Value::Int(2)]);
+        assert_eq!(eval("(1, 2)"), pair);
+        assert_eq!(eval("(1,)"), Value::Tuple(vec![Value::Int(1)]));
+    }
+
+    #[test]
+    fn slices_of_lists_and_strings() {
+        let xs = "xs = [0, 1, 2, 3, 4]\n";
+        let slice = |s: &str| eval(&format!("{xs}{s}"));
+        assert_eq!(slice("xs[1:3]"), ints(&[1, 2]));
+        assert_eq!(slice("xs[:2]"), ints(&[0, 1]));
+        assert_eq!(slice("xs[3:]"), ints(&[3, 4]));
+        assert_eq!(slice("xs[::2]"), ints(&[0, 2, 4]));
+        assert_eq!(slice("xs[-2:]"), ints(&[3, 4]));
+        assert_eq!(slice("xs[::-1]"), ints(&[4, 3, 2, 1, 0]));
+        assert_eq!(eval("\"hello\"[1:4]"), Value::Str("ell".to_string()));
+    }
+
+    #[test]
+    fn structs_construct_and_read_fields() {
+        let point = "struct Point: x: int, y\np = Point(x=1, y=\"b\")\n";
+        assert_eq!(eval(&format!("{point}p.x")), Value::Int(1));
+        assert_eq!(eval(&format!("{point}p.y")), Value::Str("b".to_string()));
+        let src = format!("{point}p.z");
+        let missing = run_source(&src, &mut Environment::new());
+        assert!(
+            matches!(missing, Err(RunError::Eval(EvalError::NoField { .. }))),
+            "{missing:?}"
+        );
+    }
+
+    fn repeat(s: &str, n: i64) -> Result<Value, EvalError> {
+        return eval_binary(
+            &Token::Mul,
+            Value::Str(s.to_string()),
+            Value::Int(n),
+        );
+    }
+
+    #[test]
+    fn string_repetition() {
+        assert_eq!(repeat("ab", 3).unwrap(), Value::Str("ababab".to_string()));
+        assert_eq!(repeat("ab", -1).unwrap(), Value::Str(String::new()));
+        assert_eq!(repeat("", i64::MAX).unwrap(), Value::Str(String::new()));
+        assert_eq!(run("print(2 * \"ab\" + \"c\")").unwrap(), "ababc\n");
+    }
+
+    #[test]
+    fn string_repetition_too_long_is_an_error() {
+        for n in [i64::MAX, 1 << 62, 1 << 28] {
+            assert!(matches!(
+                repeat("ab", n),
+                Err(EvalError::InvalidOperand(_))
+            ));
+        }
+        assert!(run("x = \"ab\" * 9223372036854775807").is_err());
+    }
+
+    #[test]
+    fn slice_with_huge_positive_step() {
+        let sliced =
+            eval_slice(ints(&[1, 2, 3]), Some(1), None, Some(i64::MAX));
+        assert_eq!(sliced.unwrap(), ints(&[2]));
+        let src = "xs = [1, 2, 3]\nprint(xs[1::9223372036854775807])";
+        assert_eq!(run(src).unwrap(), "[2]\n");
+    }
+
+    #[test]
+    fn slice_with_huge_negative_step() {
+        let sliced = eval_slice(ints(&[1, 2, 3]), None, None, Some(i64::MIN));
+        assert_eq!(sliced.unwrap(), ints(&[3]));
+        let src = "xs = [1, 2, 3]\nprint(xs[1::-9223372036854775807])";
+        assert_eq!(run(src).unwrap(), "[2]\n");
+    }
+}
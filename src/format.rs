@@ -0,0 +1,171 @@
+use std::fmt::Display;
+
+use crate::ast::Node;
+use crate::parser::{ParseError, Parser};
+use crate::token::Tokenizer;
+
+/// What one level of indentation is written as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndentStyle {
+    Spaces(usize),
+    Tab,
+}
+
+/// Layout choices for turning a parsed tree back into source
+pub struct FormatOptions {
+    pub indent: IndentStyle,
+    /// Whether binary operators and assignments get a space either side
+    pub spaced_ops: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        return FormatOptions {
+            indent: IndentStyle::Spaces(4),
+            spaced_ops: true,
+        };
+    }
+}
+
+impl FormatOptions {
+    /// Leading whitespace for a line `level` blocks deep
+    pub fn indent(&self, level: usize) -> String {
+        return match self.indent {
+            IndentStyle::Spaces(width) => " ".repeat(width * level),
+            IndentStyle::Tab => "\t".repeat(level),
+        };
+    }
+    /// An infix operator, padded if the options ask for it
+    pub fn op(&self, op: &impl Display) -> String {
+        if self.spaced_ops {
+            return format!(" {op} ");
+        }
+        return op.to_string();
+    }
+}
+
+/// Emits normalized source for `node`, so any two spellings of the same
+/// program come out identical
+pub fn format_node(node: &dyn Node, opts: FormatOptions) -> String {
+    return node.format(&opts);
+}
+
+/// Why formatted source didn't parse back into the same program
+#[derive(Debug)]
+pub enum RoundTripError {
+    /// The original source didn't parse, so there was nothing to format
+    Parse(ParseError),
+    /// The formatted source, unlike the original, failed to parse
+    Reparse { formatted: String, error: ParseError },
+    /// Formatting the reparsed program gave something else
+    Unstable { first: String, second: String },
+}
+
+/// Formats `src`, then parses and formats the result again, checking the
+/// second pass changes nothing. Anything else means format wrote source
+/// the parser reads differently. Returns the formatted source
+pub fn check_round_trip(
+    src: &str,
+    opts: &FormatOptions,
+) -> Result<String, RoundTripError> {
+    let parse = |src: &str| {
+        return Parser::from_lexer(Tokenizer::new(src.to_string()))
+            .parse_program();
+    };
+    let first = parse(src).map_err(RoundTripError::Parse)?.format(opts);
+    let second = match parse(&first) {
+        Ok(program) => program.format(opts),
+        Err(error) => {
+            return Err(RoundTripError::Reparse {
+                formatted: first,
+                error,
+            });
+        }
+    };
+    if first != second {
+        return Err(RoundTripError::Unstable { first, second });
+    }
+    return Ok(first);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::token::Tokenizer;
+
+    fn format(src: &str, opts: FormatOptions) -> String {
+        let tokens = Tokenizer::new(src.to_string()).tokenize_spanned();
+        let program = Parser::new(tokens.expect("test source lexes"))
+            .parse_program()
+            .expect("test source parses");
+        return format_node(&program, opts);
+    }
+
+    #[test]
+    fn spacing_is_normalized() {
+        let spaced = format("x = 2 + 3", FormatOptions::default());
+        assert_eq!(spaced, "x = (2 + 3)");
+        assert_eq!(format("x=2+3", FormatOptions::default()), spaced);
+        let packed = FormatOptions {
+            spaced_ops: false,
+            ..FormatOptions::default()
+        };
+        assert_eq!(format("x = 2 + 3", packed), "x=(2+3)");
+    }
+
+    fn indented(indent: IndentStyle) -> FormatOptions {
+        return FormatOptions {
+            indent,
+            ..FormatOptions::default()
+        };
+    }
+
+    #[test]
+    fn indent_width() {
+        let src = "if a:\n    if b:\n        c\n    else:\n        d";
+        assert_eq!(
+            format(src, indented(IndentStyle::Spaces(2))),
+            "if a:\n  if b:\n    c\n  else:\n    d"
+        );
+        assert_eq!(format(src, indented(IndentStyle::Spaces(4))), src);
+        assert_eq!(
+            format(src, indented(IndentStyle::Tab)),
+            "if a:\n\tif b:\n\t\tc\n\telse:\n\t\td"
+        );
+    }
+
+    #[test]
+    fn repr_round_trips() {
+        let snippets = [
+            "x = 2 + 3 * 4 - -1",
+            "y = (2 ** 3) ** 2 % 5",
+            "-(2 ** 2)",
+            "(-2) ** 2",
+            "a and not b or c == d",
+            "f(1, g(2), k=3)",
+            "1 |> f |> g",
+            "z = \"a\\tb\\n\\\"q\\\"\" + 'c'",
+            "l = [1, [2.5, 1e10], none, true]",
+            "m = {\"a\": (1,), \"b\": ()}",
+            "s = l[1:][::2][0]",
+            "v = 1 if a else 2 if b else 3",
+            "w = (x := 2) + 1",
+            "f = (x, y) -> x + y",
+            "a, b = b, a",
+            "mut n: int = 0\nn += 1",
+            "def f(x: int, y = 2) -> int:\n    return x + y",
+            "if a:\n    b\nelif c:\n    d\nelse:\n    e",
+            "while n < 3:\n    n += 1\n    if n == 2:\n        continue\n    \
+             break",
+            "for x in [1, 2]:\n    print(x)",
+            "match x:\n    case -1:\n        a\n    case _:\n        b",
+            "struct P: x: int, y\np = P(x=1, y=2)\nprint(p.x)",
+        ];
+        for src in snippets {
+            if let Err(e) = check_round_trip(src, &FormatOptions::default()) {
+                panic!("{src:?} doesn't round trip: {e:?}");
+            }
+        }
+    }
+}
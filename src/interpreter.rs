@@ -0,0 +1,87 @@
+use std::io::{self, Write};
+
+use crate::ast::{BlockStmt, Node};
+use crate::eval::{Environment, EvalError, Limits, Value};
+use crate::runner::{run_source, RunError};
+
+/// Runs programs one after another against a single environment, so each
+/// sees whatever the ones before it bound. Where output goes and how far
+/// programs may run are fixed when it's made
+pub struct Interpreter {
+    env: Environment,
+}
+
+impl Default for Interpreter {
+    /// Prints to stdout, with the default limits
+    fn default() -> Self {
+        return Interpreter::new(Box::new(io::stdout()), Limits::default());
+    }
+}
+
+impl Interpreter {
+    pub fn new(output: Box<dyn Write>, limits: Limits) -> Self {
+        return Interpreter {
+            env: Environment::with_config(output, limits),
+        };
+    }
+    pub fn env(&self) -> &Environment {
+        return &self.env;
+    }
+    pub fn output(&mut self) -> &mut dyn Write {
+        return self.env.output();
+    }
+    pub fn eval_node(&mut self, node: &dyn Node) -> Result<Value, EvalError> {
+        return node.eval(&mut self.env);
+    }
+    /// Evaluates every statement of `program`, producing the last one's
+    /// result
+    pub fn eval_program(
+        &mut self,
+        program: &BlockStmt,
+    ) -> Result<Value, EvalError> {
+        return self.eval_node(program);
+    }
+    /// Tokenizes, parses and evaluates `src`
+    pub fn run_source(&mut self, src: &str) -> Result<Value, RunError> {
+        return run_source(src, &mut self.env);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn output_goes_where_the_interpreter_was_told() {
+        let (mut interpreter, output) = testing::interpreter();
+        interpreter
+            .run_source("print(\"a\", 1)\nfor x in [2, 3]:\n    print(x)")
+            .unwrap();
+        assert_eq!(output.contents().as_bytes(), b"a 1\n2\n3\n");
+    }
+
+    #[test]
+    fn bindings_carry_over_between_programs() {
+        let (mut interpreter, output) = testing::interpreter();
+        interpreter.run_source("mut x = 40").unwrap();
+        assert_eq!(interpreter.env().get("x"), Some(&Value::Int(40)));
+        interpreter.run_source("x += 2\nprint(x)").unwrap();
+        assert_eq!(output.contents(), "42\n");
+        assert_eq!(interpreter.run_source("x").unwrap(), Value::Int(42));
+    }
+
+    #[test]
+    fn recursion_stops_at_the_limit() {
+        let limits = Limits {
+            recursion: Some(50),
+            ..Limits::default()
+        };
+        let mut interpreter = Interpreter::new(Box::new(io::sink()), limits);
+        let src = "def f(n):\n    return f(n + 1)\nf(0)";
+        assert!(matches!(
+            interpreter.run_source(src),
+            Err(RunError::Eval(EvalError::RecursionLimit(50)))
+        ));
+    }
+}
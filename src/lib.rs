@@ -0,0 +1,14 @@
+#![allow(clippy::needless_return)]
+
+pub mod ast;
+pub mod builtins;
+pub mod eval;
+pub mod format;
+pub mod interpreter;
+pub mod parser;
+pub mod repl;
+pub mod runner;
+#[cfg(test)]
+mod testing;
+pub mod token;
+pub mod typecheck;
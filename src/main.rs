@@ -1,12 +1,23 @@
-pub mod ast;
-pub mod parser;
-pub mod token;
+#![allow(clippy::needless_return)]
 
-use crate::ast::Node;
-use parser::Parser;
-use token::Tokenizer;
+use interp_rs::ast::Node;
+use interp_rs::parser::Parser;
+use interp_rs::token::Tokenizer;
+use interp_rs::{repl, runner};
+use std::path::Path;
 
 fn main() {
+    match std::env::args().nth(1).as_deref() {
+        Some("repl") => return repl::run_repl(),
+        Some(path) => {
+            if let Err(e) = runner::run_file(Path::new(path)) {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        None => {}
+    }
     let _ = String::from(
         "struct Point:
     x: int
@@ -80,19 +91,25 @@ a
 
 ",
     );
-    let tokens = Tokenizer::new(src).tokenize().expect("Failed to tokenize");
+    let tokens = match Tokenizer::new(src).tokenize_spanned() {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            println!("{e}");
+            return;
+        }
+    };
 
-    for (ix, token) in tokens.iter().enumerate() {
+    for (ix, (_, token)) in tokens.iter().enumerate() {
         println!("{ix} {}", token);
     }
     let mut parser = Parser::new(tokens);
-    let ast = parser.parse_block(0);
+    let ast = parser.parse_program();
     match ast {
         Ok(val) => {
             println!("{}", &val.repr());
         }
         Err(e) => {
-            println!("{:?}", e);
+            println!("{e}");
         }
     }
     // let result = Evaluator::new(ast).eval();
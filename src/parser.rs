@@ -1,259 +1,879 @@
+use std::collections::VecDeque;
+use std::fmt;
+
 use crate::{
     ast::{
-        AssignmentStmt, BinaryExpr, BlockStmt, CallStmt, ConditionalStmt,
-        FnLiteral, Identifier, IntegerNode, Node, ReturnStmt,
+        AssignmentStmt, BinaryExpr, BlockStmt, BooleanNode, BreakStmt,
+        CallStmt, CharNode, ConditionalStmt, ContinueStmt, ElseBranch,
+        FieldAccess, FloatNode, FnArg, FnLiteral, ForStmt, Identifier,
+        IndexExpr, IntegerNode, LambdaNode, ListNode, LogicalExpr, MapEntry,
+        MapNode, MatchStmt, NamedArg, Node, NoneNode, Pattern, ReturnStmt,
+        SliceExpr, StringNode, StructDef, TernaryExpr, TupleNode, UnaryExpr,
+        UnpackAssignStmt, WalrusExpr, WhileStmt,
+    },
+    token::{
+        Span, SpannedToken, Token, TokenStream, Tokenizer, TokenizerError,
     },
-    token::Token,
 };
 
+/// The positional and then the named arguments between a call's parens
+type CallArgs = (Vec<Box<dyn Node>>, Vec<NamedArg>);
+
 #[derive(Debug)]
 pub enum ParseError {
     InvalidTypeData(String),
     InvalidIndentLevel(String),
     InvalidTokenOrder(String),
+    UnexpectedToken {
+        expected: String,
+        found: Token,
+        span: Span,
+    },
     NotImplementedToken(String),
-    ReachedEnd,
     InvalidBlockStart(String),
-    UnclosedParen,
+    UnclosedParen {
+        open_span: Span,
+    },
+    UnmatchedParen {
+        span: Span,
+    },
+    // Nesting deeper than the parser's depth limit, at the token where it
+    // went over
+    TooDeep {
+        limit: usize,
+        span: Span,
+    },
+    // More binary operators in one statement than MAX_OPERATORS, at the one
+    // that went over
+    TooLong {
+        limit: usize,
+        span: Span,
+    },
+    Tokenize(TokenizerError),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Self::InvalidTypeData(reason) => {
+                write!(f, "invalid literal: {reason}")
+            }
+            Self::InvalidIndentLevel(reason) => {
+                write!(f, "invalid indent level: {reason}")
+            }
+            Self::InvalidTokenOrder(reason) => {
+                write!(f, "invalid token order: {reason}")
+            }
+            Self::UnexpectedToken {
+                expected,
+                found,
+                span,
+            } => {
+                write!(f, "expected {expected} but found {found} at {span}")
+            }
+            Self::NotImplementedToken(tok) => {
+                write!(f, "{tok} is not supported yet")
+            }
+            Self::InvalidBlockStart(reason) => {
+                write!(f, "invalid block start: {reason}")
+            }
+            Self::UnclosedParen { open_span } => {
+                write!(f, "paren opened at {open_span} is never closed")
+            }
+            Self::UnmatchedParen { span } => {
+                write!(f, "closing paren at {span} has no opening paren")
+            }
+            Self::TooDeep { limit, span } => {
+                write!(f, "nested more than {limit} deep at {span}")
+            }
+            Self::TooLong { limit, span } => {
+                write!(
+                    f,
+                    "more than {limit} operators in a statement at {span}"
+                )
+            }
+            Self::Tokenize(e) => write!(f, "{e}"),
+        };
+    }
 }
 
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        return match self {
+            Self::Tokenize(e) => Some(e),
+            _ => None,
+        };
+    }
+}
+
+/// How many tokens a streaming parser keeps buffered: the current one and
+/// one of lookahead
+const LOOKAHEAD: usize = 2;
+
+/// How deep expressions and blocks may nest before parsing fails. Parsing,
+/// evaluating and even dropping a tree recurse through it, so anything
+/// much deeper could overflow the stack
+pub const DEFAULT_DEPTH_LIMIT: usize = 256;
+
+/// How many binary operators one statement may hold. A chain of them is
+/// flat in the source but a tree as deep as the chain is long, which the
+/// depth limit doesn't see
+pub const MAX_OPERATORS: usize = 4096;
+
 pub struct Parser {
-    tokens: Vec<Token>,
-    n_tokens: usize,
-    l: usize,
+    // tokens[0] is the current token, anything after it is lookahead
+    tokens: VecDeque<SpannedToken>,
+    // Set when parsing straight from a Tokenizer, tokens are then pulled
+    // from it as the cursor moves instead of all being lexed up front
+    stream: Option<TokenStream>,
+    lex_error: Option<TokenizerError>,
+    // Tokens stepped over so far
     r: usize,
+    // How many parens are open around the current token
+    paren_depth: usize,
+    // How many loops the current statement is inside, in the current
+    // function
+    loop_depth: usize,
+    // How deep the tree being built is nested at the current token
+    depth: usize,
+    depth_limit: usize,
+    // Binary operators taken so far in the current statement
+    operators: usize,
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
 pub enum Precedence {
     Lowest,
+    Ternary,
     Pipe,
+    Or,
+    And,
     EqNotEq,
     LtGt,
     AddSub,
     MulDiv,
+    Prefix,
+    Pow,
+    Index,
+}
+
+impl Precedence {
+    /// Every level, loosest first, in the order they are declared in
+    const LEVELS: [Precedence; 12] = [
+        Precedence::Lowest,
+        Precedence::Ternary,
+        Precedence::Pipe,
+        Precedence::Or,
+        Precedence::And,
+        Precedence::EqNotEq,
+        Precedence::LtGt,
+        Precedence::AddSub,
+        Precedence::MulDiv,
+        Precedence::Prefix,
+        Precedence::Pow,
+        Precedence::Index,
+    ];
+    /// The next tighter level, or Index, the tightest, for Index itself
+    pub fn higher(self) -> Precedence {
+        let ix = (self as usize + 1).min(Self::LEVELS.len() - 1);
+        return Self::LEVELS[ix];
+    }
+    /// The next looser level, or Lowest for Lowest itself
+    pub fn lower(self) -> Precedence {
+        return Self::LEVELS[(self as usize).saturating_sub(1)];
+    }
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
+    pub fn new(mut tokens: Vec<SpannedToken>) -> Self {
+        // Running out of input always shows up as an Eof token, so a
+        // missing one is added rather than checked for everywhere
+        if tokens.last().is_none_or(|(_, tok)| *tok != Token::Eof) {
+            let span =
+                tokens.last().map(|(span, _)| *span).unwrap_or_default();
+            tokens.push((span, Token::Eof));
+        }
         return Parser {
-            n_tokens: tokens.len(),
-            tokens: tokens,
-            l: 0,
+            tokens: tokens.into(),
+            stream: None,
+            lex_error: None,
             r: 0,
+            paren_depth: 0,
+            loop_depth: 0,
+            depth: 0,
+            depth_limit: DEFAULT_DEPTH_LIMIT,
+            operators: 0,
         };
     }
-    fn step(&mut self) {
-        self.r += 1;
-        self.l = self.r;
+    /// Parses while lexing, holding only a few tokens at a time rather than
+    /// the whole token stream
+    pub fn from_lexer(lexer: Tokenizer) -> Self {
+        let mut parser = Parser {
+            tokens: VecDeque::with_capacity(LOOKAHEAD),
+            stream: Some(TokenStream::new(lexer)),
+            lex_error: None,
+            r: 0,
+            paren_depth: 0,
+            loop_depth: 0,
+            depth: 0,
+            depth_limit: DEFAULT_DEPTH_LIMIT,
+            operators: 0,
+        };
+        parser.fill();
+        return parser;
     }
-    fn incr_leading(&mut self) -> Result<(), ParseError> {
-        if self.r == self.n_tokens {
-            return Err(ParseError::ReachedEnd);
+    /// Fails with TooDeep past `limit` levels of nesting rather than
+    /// DEFAULT_DEPTH_LIMIT
+    pub fn with_depth_limit(mut self, limit: usize) -> Self {
+        self.depth_limit = limit;
+        return self;
+    }
+    /// Goes a level deeper, failing if that is past the limit
+    fn enter(&mut self) -> Result<(), ParseError> {
+        self.depth += 1;
+        if self.depth > self.depth_limit {
+            return Err(ParseError::TooDeep {
+                limit: self.depth_limit,
+                span: self.tokens[0].0,
+            });
         }
-
-        self.r += 1;
         return Ok(());
     }
-
-    fn incr_trailing(&mut self) {
-        self.l += 1;
+    /// Steps over a binary operator, failing if the current statement
+    /// already has too many. A chain like `1 + 2 + 3` only nests as deep as
+    /// its brackets in the source, but builds a tree as deep as it is long
+    fn take_operator(&mut self) -> Result<(), ParseError> {
+        self.operators += 1;
+        if self.operators > MAX_OPERATORS {
+            return Err(ParseError::TooLong {
+                limit: MAX_OPERATORS,
+                span: self.tokens[0].0,
+            });
+        }
+        self.step();
+        return Ok(());
+    }
+    /// Runs `parse` a level deeper than the current one
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        self.enter()?;
+        let parsed = parse(self);
+        self.depth -= 1;
+        return parsed;
+    }
+    /// Tops the lookahead up from the stream, if there is one
+    fn fill(&mut self) {
+        while self.tokens.len() < LOOKAHEAD {
+            let Some(stream) = self.stream.as_mut() else {
+                return;
+            };
+            match stream.next() {
+                Some(Ok(token)) => self.tokens.push_back(token),
+                Some(Err(e)) => {
+                    // Input ends where lexing failed, parse_program reports
+                    // the error once the parser runs into that end
+                    let span = self.tokens.back().map(|(span, _)| *span);
+                    self.tokens
+                        .push_back((span.unwrap_or_default(), Token::Eof));
+                    self.lex_error = Some(e);
+                    self.stream = None;
+                }
+                None => self.stream = None,
+            }
+        }
+    }
+    fn step(&mut self) {
+        // Never move past Eof, which is always the last token
+        if self.tokens.len() > 1 {
+            self.tokens.pop_front();
+            self.r += 1;
+            self.fill();
+        }
     }
 
     pub fn parse_stmt(
         &mut self,
-        precedence: Precedence,
-        terminator: Token,
         indent: usize,
     ) -> Result<Option<Box<dyn Node>>, ParseError> {
-        let mut node: Option<Box<dyn Node>> = None;
-        while self.r < self.n_tokens
-            && !self.current_token_is(terminator.clone())?
-            && !self.current_token_is(Token::Eof)?
-        {
-            let tok = self.get_token();
-            println!("[Indent {indent}] - {} {tok}", self.r);
-            match tok {
-                // TokenType::Pipe => {
-                //     return Ok(Some(self.parse_pipe_expr()?));
-                // }
-                Token::Indent(_) => {
-                    break;
-                }
-                Token::Add
-                | Token::Sub
-                | Token::Mul
-                | Token::Div
-                | Token::Eq
-                | Token::Gt
-                | Token::GtEq
-                | Token::Pipe
-                | Token::PipeMethod
-                | Token::Lt
-                | Token::LtEq => {
-                    let new_precedence = Self::get_precedence(&tok);
-                    if new_precedence <= precedence {
-                        if node.is_none() {
-                            node = self.get_operand_node()?;
-                        }
-                        return Ok(node);
-                    }
-                    if node.is_none() {
-                        node = self.get_operand_node()?;
-                    }
-                    self.step();
-                    node = Some(Self::get_binary_node(
-                        tok.clone(),
-                        node.unwrap(),
-                        self.parse_stmt(
-                            new_precedence,
-                            terminator.clone(),
-                            indent,
-                        )?
-                        .expect("Unpack Binary"),
-                    )?);
-                }
-                Token::LParen => {
-                    self.incr_leading()?;
-                    node = self.parse_stmt(
-                        Precedence::Lowest,
-                        terminator.clone(),
-                        indent,
-                    )?;
-                }
-                Token::RParen => {
-                    if node.is_none() {
-                        node = self.get_operand_node()?;
-                    }
-                    self.incr_leading()?;
-                    return Ok(node);
-                }
-                Token::ReverseWalrus => {
-                    let identifier: Identifier =
-                        Identifier::new(self.tokens[self.l].to_string());
-                    self.step();
-                    self.step();
-                    let expr = self.parse_stmt(
-                        Precedence::Lowest,
-                        Token::Indent(indent),
-                        indent,
-                    )?;
-                    return Ok(Some(Box::new(AssignmentStmt::new(
-                        identifier,
-                        expr.expect("Unwrap Assignment expr"),
-                    ))));
-                }
-                Token::Assignment => {
-                    let identifier: Identifier =
-                        Identifier::new(self.tokens[self.l].to_string());
-                    self.step();
-                    let expr = self.parse_stmt(
-                        Precedence::Lowest,
-                        // TokenType::Newline,
-                        Token::Indent(indent),
-                        indent,
-                    )?;
-                    return Ok(Some(Box::new(AssignmentStmt::new(
-                        identifier,
-                        expr.expect("Unwrap Assignment expr"),
-                    ))));
-                }
-                Token::Def => {
-                    // Get fn name
-                    return Ok(Some(self.parse_fn(indent)?));
-                }
-                Token::Return => {
-                    self.step();
-                    return Ok(Some(Box::new(ReturnStmt::new(
-                        self.parse_stmt(
-                            Precedence::Lowest,
-                            // TokenType::Newline,
-                            Token::Indent(indent),
-                            indent,
-                        )?
-                        .expect("Return Stmt"),
-                    ))));
-                }
-                Token::If => {
-                    return Ok(Some(self.parse_conditional_stmt(indent)?));
-                }
-                Token::Identifier(ident) => {
-                    if self.can_peek() && self.peek_token_is(Token::LParen)? {
-                        self.step();
-                        let args = self.parse_call_args()?;
-                        node = Some(Box::new(CallStmt::new(
-                            Identifier::new(ident),
-                            args,
-                        )));
-                    } else {
-                        self.incr_leading()?;
-                    }
+        self.operators = 0;
+        let tok = self.get_token();
+        match tok {
+            // Blank line
+            Token::Indent(_) | Token::Eof => {
+                return Ok(None);
+            }
+            Token::Identifier(_)
+                if self.peek_token_is(Token::ReverseWalrus)
+                    || self.peek_token_is(Token::Assignment)
+                    || self.peek_token_is(Token::Colon) =>
+            {
+                return Ok(Some(self.parse_assignment(indent, false)?));
+            }
+            Token::Identifier(_) if self.peek_token_is(Token::Comma) => {
+                return Ok(Some(self.parse_unpack_assignment()?));
+            }
+            Token::Mut => {
+                self.step();
+                return Ok(Some(self.parse_assignment(indent, true)?));
+            }
+            Token::Identifier(ident)
+                if self.peek().and_then(Self::get_compound_op).is_some() =>
+            {
+                // `x op= expr` is sugar for `x = x op expr`
+                self.step();
+                let (span, _) = self.tokens[0];
+                let op = Self::get_compound_op(&self.get_token())
+                    .ok_or_else(|| self.unexpected("Compound Assignment"))?;
+                self.step();
+                let expr = self.parse_expr(Precedence::Lowest)?;
+                let current = Box::new(Identifier::new(ident.clone()));
+                return Ok(Some(Box::new(AssignmentStmt::new(
+                    Identifier::new(ident),
+                    None,
+                    Box::new(BinaryExpr::new(op, current, expr, span)),
+                    false,
+                ))));
+            }
+            Token::Def => {
+                return Ok(Some(self.parse_fn(indent)?));
+            }
+            Token::Struct => {
+                return Ok(Some(self.parse_struct()?));
+            }
+            Token::Return => {
+                self.step();
+                let expr = self.parse_expr(Precedence::Lowest)?;
+                return Ok(Some(Box::new(ReturnStmt::new(expr))));
+            }
+            Token::If => {
+                return Ok(Some(self.parse_conditional_stmt(indent)?));
+            }
+            Token::While => {
+                return Ok(Some(self.parse_while_stmt(indent)?));
+            }
+            Token::Match => {
+                return Ok(Some(self.parse_match_stmt(indent)?));
+            }
+            Token::For => {
+                return Ok(Some(self.parse_for_stmt(indent)?));
+            }
+            Token::Break | Token::Continue => {
+                if self.loop_depth == 0 {
+                    return Err(ParseError::InvalidTokenOrder(format!(
+                        "{tok} Outside Loop"
+                    )));
                 }
-                _ => {
-                    self.incr_leading()?;
+                self.step();
+                if tok == Token::Break {
+                    return Ok(Some(Box::new(BreakStmt)));
                 }
+                return Ok(Some(Box::new(ContinueStmt)));
+            }
+            _ => {
+                return Ok(Some(self.parse_expr(Precedence::Lowest)?));
             }
         }
-        if node.is_none() {
-            node = self.get_operand_node()?;
+    }
+
+    /// Parses `name = expr`, or the block form `name =:` with the
+    /// expression on the next line, starting from the name. The name may be
+    /// annotated as in `name: mut int = expr`, where both the `mut` and the
+    /// type are optional
+    fn parse_assignment(
+        &mut self,
+        indent: usize,
+        mut mutable: bool,
+    ) -> Result<Box<AssignmentStmt>, ParseError> {
+        let Token::Identifier(ident) = self.get_token() else {
+            return Err(self.unexpected("Identifier"));
+        };
+        self.step();
+        let mut annotation = None;
+        if self.current_token_is(Token::Colon) {
+            self.step();
+            if self.current_token_is(Token::Mut) {
+                mutable = true;
+                self.step();
+            }
+            if let Token::Identifier(_) = self.get_token() {
+                annotation = Some(self.parse_type_name()?);
+            }
         }
-        return Ok(node);
+        match self.get_token() {
+            Token::Assignment => self.step(),
+            Token::ReverseWalrus => {
+                // Block assignment, the expression starts on the next line
+                self.expect_peek(Token::Indent(indent + 1))?;
+                self.step();
+                self.step();
+            }
+            _ => return Err(self.unexpected("Assignment")),
+        }
+        let expr = self.parse_expr(Precedence::Lowest)?;
+        return Ok(Box::new(AssignmentStmt::new(
+            Identifier::new(ident),
+            annotation,
+            expr,
+            mutable,
+        )));
+    }
+
+    /// Parses `a, b = x, y`, starting from the first name
+    fn parse_unpack_assignment(
+        &mut self,
+    ) -> Result<Box<UnpackAssignStmt>, ParseError> {
+        let mut targets = Vec::new();
+        loop {
+            let Token::Identifier(name) = self.get_token() else {
+                return Err(self.unexpected("Identifier"));
+            };
+            targets.push(Identifier::new(name));
+            self.step();
+            if !self.current_token_is(Token::Comma) {
+                break;
+            }
+            self.step();
+        }
+        if !self.current_token_is(Token::Assignment) {
+            return Err(self.unexpected("Assignment"));
+        }
+        let mut exprs = Vec::new();
+        loop {
+            self.step();
+            exprs.push(self.parse_expr(Precedence::Lowest)?);
+            if !self.current_token_is(Token::Comma) {
+                break;
+            }
+        }
+        // A single value may be a tuple or list, which is only unpacked, and
+        // checked, once it is evaluated
+        if exprs.len() > 1 && exprs.len() != targets.len() {
+            return Err(ParseError::InvalidTokenOrder(format!(
+                "Expected {} Values To Unpack - Found {}",
+                targets.len(),
+                exprs.len()
+            )));
+        }
+        return Ok(Box::new(UnpackAssignStmt::new(targets, exprs)));
+    }
+
+    /// A type in an annotation, which for now is just a name
+    fn parse_type_name(&mut self) -> Result<String, ParseError> {
+        let Token::Identifier(name) = self.get_token() else {
+            return Err(self.unexpected("Type"));
+        };
+        self.step();
+        return Ok(name);
+    }
+
+    pub fn parse_expr(
+        &mut self,
+        precedence: Precedence,
+    ) -> Result<Box<dyn Node>, ParseError> {
+        return self.nested(|parser| {
+            let node = parser.get_operand_node()?;
+            return parser.parse_infix(node, precedence);
+        });
+    }
+
+    /// Extends `node` with any binary operators binding tighter than
+    /// `precedence`
+    fn parse_infix(
+        &mut self,
+        mut node: Box<dyn Node>,
+        precedence: Precedence,
+    ) -> Result<Box<dyn Node>, ParseError> {
+        loop {
+            let tok = self.get_token();
+            // Anything that isn't a binary operator is Lowest and ends the
+            // expression, so the caller decides what is allowed to follow
+            if tok == Token::RParen && self.paren_depth == 0 {
+                let span = self.tokens[0].0;
+                return Err(ParseError::UnmatchedParen { span });
+            }
+            let new_precedence = Self::get_precedence(&tok);
+            if new_precedence <= precedence {
+                return Ok(node);
+            }
+            if tok == Token::LSquare {
+                node = self.parse_index(node)?;
+                continue;
+            }
+            if tok == Token::If {
+                node = self.parse_ternary(node)?;
+                continue;
+            }
+            if tok == Token::Dot {
+                node = self.parse_field_access(node)?;
+                continue;
+            }
+            let span = self.tokens[0].0;
+            self.take_operator()?;
+            // The right side stops at another operator of the same level,
+            // which then takes this whole node as its left side, so most
+            // operators are left associative. One level lower lets another
+            // `**` be taken into it, making `**` right associative
+            let r_precedence = match tok {
+                Token::Pow => new_precedence.lower(),
+                _ => new_precedence,
+            };
+            let r = self.parse_expr(r_precedence)?;
+            node = Self::get_binary_node(tok, node, r, span)?;
+        }
+    }
+
+    /// `then if cond else otherwise`, with `then` already parsed
+    fn parse_ternary(
+        &mut self,
+        then: Box<dyn Node>,
+    ) -> Result<Box<dyn Node>, ParseError> {
+        let span = self.tokens[0].0;
+        self.step();
+        // The condition ends at `else`, and can't hold another `if` without
+        // brackets
+        let cond = self.parse_expr(Precedence::Ternary)?;
+        if !self.current_token_is(Token::Else) {
+            return Err(self.unexpected("Else"));
+        }
+        self.step();
+        // The else side can, so a chain of them nests to the right
+        let otherwise = self.parse_expr(Precedence::Lowest)?;
+        return Ok(Box::new(TernaryExpr::new(then, cond, otherwise, span)));
     }
 
     fn parse_conditional_stmt(
         &mut self,
         indent: usize,
     ) -> Result<Box<ConditionalStmt>, ParseError> {
-        // If -> Condition
+        // If or Elif -> Condition
         self.step();
-        let cond = self.parse_stmt(Precedence::Lowest, Token::Colon, 0)?;
+        let cond = self.parse_expr(Precedence::Lowest)?;
         // Condition suffix colon -> Indent
+        if !self.current_token_is(Token::Colon) {
+            return Err(self.unexpected("Colon"));
+        }
         self.step();
 
         let pass_block = self.parse_block(indent + 1)?;
-        let mut fail_block: Option<Box<BlockStmt>> = None;
+        let mut fail_branch = None;
         if self.token_is_indent_of(indent)
-            && self.peek_token_is(Token::Else)?
+            && self.peek_token_is(Token::Elif)
+        {
+            // The rest of the chain is parsed as an `if` of its own
+            self.step();
+            let elif =
+                self.nested(|parser| parser.parse_conditional_stmt(indent))?;
+            fail_branch = Some(ElseBranch::Elif(elif));
+        } else if self.token_is_indent_of(indent)
+            && self.peek_token_is(Token::Else)
         {
             self.step();
             self.expect_peek(Token::Colon)?;
             self.step();
             self.step();
-            fail_block = Some(self.parse_block(indent + 1)?);
+            let fail_block = self.parse_block(indent + 1)?;
+            fail_branch = Some(ElseBranch::Else(fail_block));
         }
         return Ok(Box::new(ConditionalStmt::new(
-            cond.expect("Unwrap conditional"),
+            indent,
+            cond,
             pass_block,
-            fail_block,
+            fail_branch,
         )));
     }
 
+    /// Parses `match value:` and the `case pattern:` arms under it
+    fn parse_match_stmt(
+        &mut self,
+        indent: usize,
+    ) -> Result<Box<MatchStmt>, ParseError> {
+        self.step();
+        let scrutinee = self.parse_expr(Precedence::Lowest)?;
+        if !self.current_token_is(Token::Colon) {
+            return Err(self.unexpected("Colon"));
+        }
+        self.expect_peek(Token::Indent(indent + 1))?;
+        self.step();
+        let mut arms = Vec::new();
+        while self.token_is_indent_of(indent + 1) {
+            self.expect_peek(Token::Case)?;
+            self.step();
+            self.step();
+            let pattern = self.parse_pattern()?;
+            if !self.current_token_is(Token::Colon) {
+                return Err(self.unexpected("Colon"));
+            }
+            self.step();
+            arms.push((pattern, self.parse_block(indent + 2)?));
+        }
+        return Ok(Box::new(MatchStmt::new(indent, scrutinee, arms)));
+    }
+
+    /// A literal, or `_` to match anything
+    fn parse_pattern(&mut self) -> Result<Pattern, ParseError> {
+        return match self.get_token() {
+            Token::Identifier(name) if name == "_" => {
+                self.step();
+                Ok(Pattern::Wildcard)
+            }
+            // Only a number can be negated, `case -x:` isn't a literal
+            Token::Sub
+                if !matches!(
+                    self.peek(),
+                    Some(Token::Int(_) | Token::Float(_))
+                ) =>
+            {
+                self.step();
+                Err(self.unexpected("Number"))
+            }
+            // A minus and a number lex as separate tokens but parse as one
+            // negative literal
+            tok if tok.is_literal() || tok == Token::Sub => {
+                Ok(Pattern::Literal(self.get_operand_node()?))
+            }
+            _ => Err(self.unexpected("Pattern")),
+        };
+    }
+
+    fn parse_while_stmt(
+        &mut self,
+        indent: usize,
+    ) -> Result<Box<WhileStmt>, ParseError> {
+        self.step();
+        let cond = self.parse_expr(Precedence::Lowest)?;
+        if !self.current_token_is(Token::Colon) {
+            return Err(self.unexpected("Colon"));
+        }
+        self.step();
+        let body = self.parse_loop_body(indent + 1)?;
+        return Ok(Box::new(WhileStmt::new(cond, body)));
+    }
+
+    /// Parses `for name in iterable:` and the block after it
+    fn parse_for_stmt(
+        &mut self,
+        indent: usize,
+    ) -> Result<Box<ForStmt>, ParseError> {
+        self.step();
+        let Token::Identifier(var) = self.get_token() else {
+            return Err(self.unexpected("Identifier"));
+        };
+        self.step();
+        if !self.current_token_is(Token::In) {
+            return Err(self.unexpected("In"));
+        }
+        self.step();
+        let iterable = self.parse_expr(Precedence::Lowest)?;
+        if !self.current_token_is(Token::Colon) {
+            return Err(self.unexpected("Colon"));
+        }
+        self.step();
+        let body = self.parse_loop_body(indent + 1)?;
+        let var = Identifier::new(var);
+        return Ok(Box::new(ForStmt::new(var, iterable, body)));
+    }
+
+    /// A block in which `break` and `continue` are allowed
+    fn parse_loop_body(
+        &mut self,
+        indent: usize,
+    ) -> Result<Box<BlockStmt>, ParseError> {
+        self.loop_depth += 1;
+        let body = self.parse_block(indent);
+        self.loop_depth -= 1;
+        return body;
+    }
+
     fn token_is_indent_of(&self, indent: usize) -> bool {
         return match self.get_token() {
             Token::Indent(indent_lvl) => indent_lvl == indent,
             _ => false,
         };
     }
-    fn parse_call_args(&mut self) -> Result<Vec<Box<dyn Node>>, ParseError> {
-        self.incr_leading()?;
+    /// Parses `(a, b, name = c)`, where any named arguments come after
+    /// the positional ones
+    fn parse_call_args(&mut self) -> Result<CallArgs, ParseError> {
+        let open_span = self.open_paren();
         let mut args: Vec<Box<dyn Node>> = Vec::new();
-        let arg = self.parse_stmt(Precedence::Lowest, Token::Comma, 0)?;
-        if let Some(unwrap_arg) = arg {
-            args.push(unwrap_arg);
+        let mut named: Vec<NamedArg> = Vec::new();
+        while !self.current_token_is(Token::RParen) {
+            match self.get_token() {
+                Token::Identifier(name)
+                    if self.peek_token_is(Token::Assignment) =>
+                {
+                    self.step();
+                    self.step();
+                    let value = self.parse_expr(Precedence::Lowest)?;
+                    named.push((Identifier::new(name), value));
+                }
+                tok if !named.is_empty() => {
+                    return Err(ParseError::InvalidTokenOrder(format!(
+                        "Expected Named Argument - Found {tok}"
+                    )));
+                }
+                _ => args.push(self.parse_expr(Precedence::Lowest)?),
+            }
+            self.end_element(&Token::RParen, open_span)?;
         }
-
-        while self.r < self.n_tokens
-            && self.can_peek()
-            && self.peek_token_is(Token::Comma)?
-        {
-            self.incr_leading()?;
-            if let Some(arg) =
-                self.parse_stmt(Precedence::Lowest, Token::Comma, 0)?
-            {
-                args.push(arg);
+        self.close_paren(open_span)?;
+        return Ok((args, named));
+    }
+    /// Parses `[a, b, c]`, starting from the opening bracket
+    fn parse_list(&mut self) -> Result<Box<ListNode>, ParseError> {
+        let open_span = self.tokens[0].0;
+        self.step();
+        let (elements, _) = self.parse_elements(Token::RSquare, open_span)?;
+        self.step();
+        return Ok(Box::new(ListNode::new(elements)));
+    }
+    /// Comma separated `key: value` pairs between braces, which may have a
+    /// comma before the closing one
+    fn parse_map(&mut self) -> Result<Box<MapNode>, ParseError> {
+        let open_span = self.tokens[0].0;
+        self.step();
+        let mut entries: Vec<MapEntry> = Vec::new();
+        while !self.current_token_is(Token::RBrace) {
+            let key = self.parse_expr(Precedence::Lowest)?;
+            if !self.current_token_is(Token::Colon) {
+                return Err(self.unexpected("Colon"));
             }
+            self.step();
+            let value = self.parse_expr(Precedence::Lowest)?;
+            entries.push((key, value));
+            self.end_element(&Token::RBrace, open_span)?;
         }
-        return Ok(args);
+        self.step();
+        return Ok(Box::new(MapNode::new(entries)));
+    }
+    /// Parses the `[index]` or `[start:stop:step]` slice after `target`,
+    /// starting from the bracket
+    fn parse_index(
+        &mut self,
+        target: Box<dyn Node>,
+    ) -> Result<Box<dyn Node>, ParseError> {
+        let open_span = self.tokens[0].0;
+        self.step();
+        let start = self.parse_slice_bound()?;
+        let node: Box<dyn Node> = match (start, self.get_token()) {
+            (Some(index), tok) if tok != Token::Colon => {
+                Box::new(IndexExpr::new(target, index, open_span))
+            }
+            (start, _) => {
+                self.step();
+                let stop = self.parse_slice_bound()?;
+                let mut step = None;
+                if self.current_token_is(Token::Colon) {
+                    self.step();
+                    step = self.parse_slice_bound()?;
+                }
+                Box::new(SliceExpr::new(target, start, stop, step, open_span))
+            }
+        };
+        match self.get_token() {
+            Token::RSquare => self.step(),
+            Token::Indent(_) | Token::Eof => {
+                return Err(ParseError::UnclosedParen { open_span });
+            }
+            _ => return Err(self.unexpected("RSquare")),
+        }
+        return Ok(node);
+    }
+    /// One of the three parts of a slice, which is left out when the next
+    /// thing is the colon or bracket after it
+    fn parse_slice_bound(
+        &mut self,
+    ) -> Result<Option<Box<dyn Node>>, ParseError> {
+        if let Token::Colon | Token::RSquare = self.get_token() {
+            return Ok(None);
+        }
+        return Ok(Some(self.parse_expr(Precedence::Lowest)?));
+    }
+    /// Comma separated expressions up to `close`, which may have a comma
+    /// before it, and whether that comma was there. Leaves the cursor on
+    /// `close`
+    fn parse_elements(
+        &mut self,
+        close: Token,
+        open_span: Span,
+    ) -> Result<(Vec<Box<dyn Node>>, bool), ParseError> {
+        let mut elements: Vec<Box<dyn Node>> = Vec::new();
+        let mut trailing_comma = false;
+        while !self.current_token_is(close.clone()) {
+            elements.push(self.parse_expr(Precedence::Lowest)?);
+            trailing_comma = self.end_element(&close, open_span)?;
+        }
+        return Ok((elements, trailing_comma));
+    }
+    /// Steps over the comma after an element, or stops on `close`,
+    /// returning which it was
+    fn end_element(
+        &mut self,
+        close: &Token,
+        open_span: Span,
+    ) -> Result<bool, ParseError> {
+        match self.get_token() {
+            Token::Comma => {
+                self.step();
+                return Ok(true);
+            }
+            tok if tok == *close => return Ok(false),
+            // The line ended before the list did
+            Token::Indent(_) | Token::Eof => {
+                return Err(ParseError::UnclosedParen { open_span });
+            }
+            _ => return Err(self.unexpected("Comma")),
+        }
+    }
+    /// `struct Name: field, field: type`, with its fields on the one line
+    fn parse_struct(&mut self) -> Result<Box<dyn Node>, ParseError> {
+        self.step();
+        let Token::Identifier(name) = self.get_token() else {
+            return Err(self.unexpected("Identifier"));
+        };
+        self.expect_peek(Token::Colon)?;
+        self.step();
+        self.step();
+        let mut names: Vec<String> = Vec::new();
+        let mut fields = Vec::new();
+        loop {
+            let Token::Identifier(field) = self.get_token() else {
+                return Err(self.unexpected("Identifier"));
+            };
+            if names.contains(&field) {
+                return Err(ParseError::InvalidTokenOrder(format!(
+                    "Field {field} Declared Twice In {name}"
+                )));
+            }
+            self.step();
+            let mut annotation = None;
+            if self.current_token_is(Token::Colon) {
+                self.step();
+                annotation = Some(self.parse_type_name()?);
+            }
+            names.push(field.clone());
+            fields.push(FnArg::new(Identifier::new(field), annotation, None));
+            if !self.current_token_is(Token::Comma) {
+                break;
+            }
+            self.step();
+        }
+        return Ok(Box::new(StructDef::new(Identifier::new(name), fields)));
+    }
+    /// Parses the `.field` after `target`, starting from the dot
+    fn parse_field_access(
+        &mut self,
+        target: Box<dyn Node>,
+    ) -> Result<Box<FieldAccess>, ParseError> {
+        let span = self.tokens[0].0;
+        self.step();
+        let Token::Identifier(field) = self.get_token() else {
+            return Err(self.unexpected("Identifier"));
+        };
+        self.step();
+        let field = Identifier::new(field);
+        return Ok(Box::new(FieldAccess::new(target, field, span)));
     }
     fn parse_fn(
         &mut self,
@@ -268,54 +888,62 @@ impl Parser {
             self.step();
 
             let args = self.parse_args()?;
-            self.step();
-            self.expect_peek(Token::Colon)?;
-
-            self.step();
+            let mut return_type = None;
+            if self.current_token_is(Token::Arrow) {
+                self.step();
+                return_type = Some(self.parse_type_name()?);
+            }
+            if !self.current_token_is(Token::Colon) {
+                return Err(self.unexpected("Colon"));
+            }
             self.expect_peek(Token::Indent(indent + 1))?;
 
             self.step();
-            let fn_body = Some(self.parse_block(indent + 1)?);
+            // A loop around the definition can't be broken out of from
+            // inside the function
+            let loop_depth = std::mem::take(&mut self.loop_depth);
+            let fn_body = self.parse_block(indent + 1);
+            self.loop_depth = loop_depth;
+            let fn_body = fn_body?;
             return Ok(Box::new(FnLiteral::new(
                 fn_name,
                 args,
-                fn_body.unwrap(),
+                return_type,
+                fn_body,
             )));
         }
-        return Err(ParseError::InvalidTypeData(format!(
-            "Expected Identifier In Function Definition"
-        )));
+        return Err(ParseError::InvalidTypeData(
+            "Expected Identifier In Function Definition".to_string(),
+        ));
     }
     fn parse_statements(
         &mut self,
         indent: usize,
     ) -> Result<Vec<Box<dyn Node>>, ParseError> {
         let mut stmts: Vec<Box<dyn Node>> = Vec::new();
-        while self.r < self.n_tokens {
+        loop {
             let tok = self.get_token();
             if tok == Token::Eof {
                 break;
             }
-            if let Token::Indent(new_indent) = *&tok {
+            if let Token::Indent(new_indent) = tok {
                 if new_indent < indent {
-                    println!("{new_indent} <-- {indent}");
                     break;
                 } else if new_indent > indent {
-                    println!("{new_indent} --> {indent}");
                     stmts.push(self.parse_block(new_indent)?);
                 } else {
                     self.step();
-                    if let Some(stmt) = self.parse_stmt(
-                        Precedence::Lowest,
-                        Token::Indent(indent),
-                        // TokenType::Newline,
-                        indent,
-                    )? {
+                    if let Some(stmt) = self.parse_stmt(indent)? {
                         stmts.push(stmt);
                     }
                 }
+            } else if tok == Token::Semicolon {
+                // Another statement on the same line
+                self.step();
+                if let Some(stmt) = self.parse_stmt(indent)? {
+                    stmts.push(stmt);
+                }
             } else {
-                println!("Here {}", self.r);
                 return Err(ParseError::InvalidBlockStart(format!(
                     "{:?}",
                     self.get_token()
@@ -324,118 +952,858 @@ impl Parser {
         }
         return Ok(stmts);
     }
+    /// Parses a whole source file into its top-level block
+    pub fn parse_program(&mut self) -> Result<BlockStmt, ParseError> {
+        let program = self.parse_block(0).and_then(|program| {
+            if !self.current_token_is(Token::Eof) {
+                return Err(ParseError::InvalidBlockStart(format!(
+                    "Expected end of program - Found {}",
+                    self.get_token()
+                )));
+            }
+            return Ok(*program);
+        });
+        // A lex error cuts the input short, which is the real problem with
+        // whatever the parser made of it
+        if let Some(e) = self.lex_error.take() {
+            return Err(ParseError::Tokenize(e));
+        }
+        return program;
+    }
     pub fn parse_block(
         &mut self,
         indent: usize,
     ) -> Result<Box<BlockStmt>, ParseError> {
         let tok = self.get_token();
-        if let Token::Indent(ind_lvl) = *&tok {
+        if let Token::Indent(ind_lvl) = tok {
             if ind_lvl != indent {
                 return Err(ParseError::InvalidIndentLevel(format!(
                     "Expected {} - Found {}",
                     indent, ind_lvl
                 )));
             }
-            let stmts = self.parse_statements(ind_lvl)?;
+            let stmts =
+                self.nested(|parser| parser.parse_statements(ind_lvl))?;
             return Ok(Box::new(BlockStmt::new(indent, stmts)));
         }
         return Err(ParseError::InvalidBlockStart(format!(
-            "Should be unreachable {} {}/{}",
-            tok, self.r, self.n_tokens
+            "Should be unreachable {} at token {}",
+            tok, self.r
         )));
     }
 
-    fn parse_args(&mut self) -> Result<Vec<Identifier>, ParseError> {
-        // TODO: Fix
+    fn parse_args(&mut self) -> Result<Vec<FnArg>, ParseError> {
         let mut args = Vec::new();
-        let mut tok = self.get_token();
-        while *&tok != Token::RParen {
+        // Defaults are expressions, so the parens have to be tracked like
+        // those of a call
+        let open_span = self.open_paren();
+        while !self.current_token_is(Token::RParen) {
+            let Token::Identifier(name) = self.get_token() else {
+                return Err(self.unexpected("Identifier"));
+            };
             self.step();
-            tok = self.get_token();
-            args.push(Identifier::new(tok.to_string()));
-
-            let next_tok =
-                self.get_peek_token().ok_or(ParseError::ReachedEnd)?;
-
-            if next_tok == Token::RParen {
-                break;
-            } else {
-                self.expect_peek(Token::Comma)?;
+            let mut annotation = None;
+            if self.current_token_is(Token::Colon) {
+                self.step();
+                annotation = Some(self.parse_type_name()?);
+            }
+            let mut default = None;
+            if self.current_token_is(Token::Assignment) {
+                self.step();
+                default = Some(self.parse_expr(Precedence::Lowest)?);
+            } else if args.iter().any(FnArg::has_default) {
+                // A call can only leave out arguments from the end
+                return Err(ParseError::InvalidTokenOrder(format!(
+                    "Expected Default For {name} - Found {}",
+                    self.get_token()
+                )));
+            }
+            args.push(FnArg::new(Identifier::new(name), annotation, default));
+            // A trailing comma before the paren is fine
+            if self.current_token_is(Token::Comma) {
+                self.step();
+            } else if !self.current_token_is(Token::RParen) {
+                return Err(self.unexpected("Comma"));
             }
-            self.step();
         }
+        self.close_paren(open_span)?;
         return Ok(args);
     }
     fn get_token(&self) -> Token {
-        return self.tokens[self.r].clone();
+        return self.tokens[0].1.clone();
     }
-    fn get_peek_token(&self) -> Option<Token> {
-        if self.r + 1 < self.n_tokens {
-            return Some(self.tokens[self.r + 1].clone());
-        }
-        return None;
+    fn current_token_is(&self, tt: Token) -> bool {
+        return self.tokens[0].1 == tt;
     }
-    fn current_token_is(&self, tt: Token) -> Result<bool, ParseError> {
-        if self.r >= self.n_tokens {
-            return Err(ParseError::ReachedEnd);
-        }
-        return Ok(*&self.tokens[self.r] == tt);
+    /// The token `offset` places past the current one, without consuming
+    /// anything. `peek_at(0)` is the current token. A parser made with
+    /// from_lexer only buffers one token ahead, so sees nothing further
+    pub fn peek_at(&self, offset: usize) -> Option<&Token> {
+        return self.tokens.get(offset).map(|(_, tok)| tok);
     }
-    fn can_peek(&self) -> bool {
-        return self.r + 1 < self.n_tokens;
+    pub fn peek(&self) -> Option<&Token> {
+        return self.peek_at(1);
     }
-    fn peek_token_is(&self, tt: Token) -> Result<bool, ParseError> {
-        if self.r == self.n_tokens {
-            return Err(ParseError::ReachedEnd);
-        }
-        return Ok(*&self.tokens[self.r + 1] == tt);
+    fn peek_token_is(&self, tt: Token) -> bool {
+        return self.peek() == Some(&tt);
     }
     fn expect_peek(&self, tt: Token) -> Result<(), ParseError> {
-        if self.r + 1 < self.n_tokens && *&self.tokens[self.r + 1] == tt {
+        if self.peek() == Some(&tt) {
             return Ok(());
         }
-        return Err(ParseError::InvalidTokenOrder(format!(
-            "Expected {:?} Found {:?}",
-            tt,
-            self.get_peek_token()
-        )));
+        // Past the end the peek is the Eof we are sitting on
+        let (span, found) =
+            self.tokens.get(1).unwrap_or(&self.tokens[0]).clone();
+        return Err(ParseError::UnexpectedToken {
+            expected: format!("{:?}", tt),
+            found,
+            span,
+        });
+    }
+    /// Steps over an opening paren, returning its span for error reporting
+    fn open_paren(&mut self) -> Span {
+        let span = self.tokens[0].0;
+        self.paren_depth += 1;
+        self.step();
+        return span;
+    }
+    fn close_paren(&mut self, open_span: Span) -> Result<(), ParseError> {
+        match self.get_token() {
+            Token::RParen => {
+                self.paren_depth -= 1;
+                self.step();
+                return Ok(());
+            }
+            Token::Indent(_) | Token::Eof => {
+                return Err(ParseError::UnclosedParen { open_span });
+            }
+            _ => return Err(self.unexpected("RParen")),
+        }
+    }
+    fn unexpected(&self, expected: &str) -> ParseError {
+        let (span, found) = self.tokens[0].clone();
+        return ParseError::UnexpectedToken {
+            expected: expected.to_string(),
+            found,
+            span,
+        };
     }
     fn get_precedence(token_type: &Token) -> Precedence {
         match token_type {
             Token::Add | Token::Sub => Precedence::AddSub,
-            Token::Mul | Token::Div => Precedence::MulDiv,
-            Token::Lt | Token::Gt => Precedence::LtGt,
-            Token::Eq => Precedence::EqNotEq,
+            Token::Mul | Token::Div | Token::Mod => Precedence::MulDiv,
+            Token::Pow => Precedence::Pow,
+            Token::Lt | Token::Gt | Token::LtEq | Token::GtEq => {
+                Precedence::LtGt
+            }
+            Token::Eq | Token::NotEq => Precedence::EqNotEq,
+            Token::And => Precedence::And,
+            Token::Or => Precedence::Or,
             Token::Pipe | Token::PipeMethod => Precedence::Pipe,
+            Token::If => Precedence::Ternary,
+            Token::LSquare | Token::Dot => Precedence::Index,
             _ => Precedence::Lowest,
         }
     }
 
-    pub fn get_operand_node(
-        &mut self,
-    ) -> Result<Option<Box<dyn Node>>, ParseError> {
-        while self.l < self.n_tokens && self.tokens[self.l] == Token::LParen {
-            self.incr_trailing();
-        }
-        let tok = self.tokens[self.l].clone();
+    pub fn get_operand_node(&mut self) -> Result<Box<dyn Node>, ParseError> {
+        let (span, tok) = self.tokens[0].clone();
         match tok {
             Token::Int(_) => {
-                return Ok(Some(Box::new(IntegerNode::new(tok)?)));
+                self.step();
+                return Ok(Box::new(IntegerNode::new(tok)?));
+            }
+            Token::Float(_) => {
+                self.step();
+                return Ok(Box::new(FloatNode::new(tok)?));
+            }
+            Token::Str(_) => {
+                self.step();
+                return Ok(Box::new(StringNode::new(tok)?));
+            }
+            Token::Char(_) => {
+                self.step();
+                return Ok(Box::new(CharNode::new(tok)?));
+            }
+            Token::None => {
+                self.step();
+                return Ok(Box::new(NoneNode));
+            }
+            Token::True | Token::False => {
+                self.step();
+                return Ok(Box::new(BooleanNode::new(tok)?));
+            }
+            Token::Identifier(ident) => {
+                self.step();
+                if self.current_token_is(Token::Walrus) {
+                    self.step();
+                    let expr = self.parse_expr(Precedence::Lowest)?;
+                    return Ok(Box::new(WalrusExpr::new(
+                        Identifier::new(ident),
+                        expr,
+                    )));
+                }
+                if self.current_token_is(Token::LParen) {
+                    let (args, named) = self.parse_call_args()?;
+                    return Ok(Box::new(CallStmt::new(
+                        Identifier::new(ident),
+                        args,
+                        named,
+                        span,
+                    )));
+                }
+                return Ok(Box::new(Identifier::new(ident)));
             }
-            Token::Identifier(i) => {
-                return Ok(Some(Box::new(Identifier::new(i))));
+            Token::LParen => {
+                // A parenthesised expression, a tuple or a lambda's
+                // parameters, which only the `->` after them tells apart
+                let open_span = self.open_paren();
+                let (mut elements, trailing_comma) =
+                    self.parse_elements(Token::RParen, open_span)?;
+                self.close_paren(open_span)?;
+                if self.current_token_is(Token::Arrow) {
+                    self.step();
+                    let body = self.parse_expr(Precedence::Lowest)?;
+                    let lambda = LambdaNode::from_params(elements, body)?;
+                    return Ok(Box::new(lambda));
+                }
+                // Without a comma, `(1)` is only grouping
+                if elements.len() == 1 && !trailing_comma {
+                    return Ok(elements.remove(0));
+                }
+                return Ok(Box::new(TupleNode::new(elements)));
+            }
+            Token::LSquare => {
+                return Ok(self.parse_list()?);
+            }
+            Token::LBrace => {
+                return Ok(self.parse_map()?);
+            }
+            // A minus straight on a number is part of the literal, so `-5`
+            // is one node. With `**` after the number it stays a prefix op,
+            // since `-2 ** 2` is `-(2 ** 2)`
+            Token::Sub
+                if matches!(
+                    self.peek(),
+                    Some(Token::Int(_) | Token::Float(_))
+                ) =>
+            {
+                self.step();
+                let number = self.get_token();
+                if self.peek_token_is(Token::Pow) {
+                    let base = self.get_operand_node()?;
+                    let operand =
+                        self.parse_infix(base, Precedence::Prefix)?;
+                    return Ok(Box::new(UnaryExpr::new(tok, operand, span)));
+                }
+                self.step();
+                let literal = format!("-{number}");
+                if let Token::Int(_) = number {
+                    return Ok(Box::new(IntegerNode::new(Token::Int(
+                        literal,
+                    ))?));
+                }
+                return Ok(Box::new(FloatNode::new(Token::Float(literal))?));
+            }
+            Token::Sub | Token::Bang | Token::Not => {
+                self.step();
+                let operand = self.parse_expr(Precedence::Prefix)?;
+                return Ok(Box::new(UnaryExpr::new(tok, operand, span)));
             }
             _ => {
-                return Ok(None);
+                return Err(self.unexpected("Expression"));
             }
         }
     }
 
+    /// The operator behind a compound assignment token like `+=`
+    fn get_compound_op(tok: &Token) -> Option<Token> {
+        return match tok {
+            Token::AddEq => Some(Token::Add),
+            Token::SubEq => Some(Token::Sub),
+            Token::MulEq => Some(Token::Mul),
+            Token::DivEq => Some(Token::Div),
+            Token::ModEq => Some(Token::Mod),
+            _ => None,
+        };
+    }
+
     pub fn get_binary_node(
         op_token: Token,
         l: Box<dyn Node>,
         r: Box<dyn Node>,
+        span: Span,
     ) -> Result<Box<dyn Node>, ParseError> {
-        return Ok(Box::new(BinaryExpr::new(op_token, l, r)));
+        if op_token == Token::Pipe {
+            return Ok(Box::new(CallStmt::from_pipe(l, r, span)?));
+        }
+        if let Token::And | Token::Or = op_token {
+            return Ok(Box::new(LogicalExpr::new(op_token, l, r, span)));
+        }
+        return Ok(Box::new(BinaryExpr::new(op_token, l, r, span)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ParseError, Parser, Precedence, DEFAULT_DEPTH_LIMIT, LOOKAHEAD,
+        MAX_OPERATORS,
+    };
+    use crate::ast::{BlockStmt, Node};
+    use crate::eval::{Environment, EvalError};
+    use crate::runner::{run_source, RunError};
+    use crate::testing::run;
+    use crate::token::{Span, Token, Tokenizer, TokenizerError};
+
+    fn parse(src: &str, depth_limit: usize) -> Result<BlockStmt, ParseError> {
+        return Parser::from_lexer(Tokenizer::new(src.to_string()))
+            .with_depth_limit(depth_limit)
+            .parse_program();
+    }
+
+    fn chain(terms: usize) -> String {
+        return format!("x = {}\n", vec!["1"; terms].join(" + "));
+    }
+
+    fn repr(src: &str) -> String {
+        return parse(src, DEFAULT_DEPTH_LIMIT)
+            .expect("test source parses")
+            .repr();
+    }
+
+    /// Runs `src`, giving back the value of its last statement as it would
+    /// print
+    fn eval(src: &str) -> String {
+        let value = run_source(src, &mut Environment::new());
+        return value.expect("test source runs").to_string();
+    }
+
+    #[test]
+    fn comparison_binds_looser_than_arithmetic() {
+        assert_eq!(repr("1 + 2 == 3"), "((1 + 2) == 3)");
+        assert_eq!(repr("1 < 2 and 3 != 4"), "((1 < 2) and (3 != 4))");
+    }
+
+    #[test]
+    fn prefix_operators() {
+        assert_eq!(repr("-x"), "(-x)");
+        assert_eq!(repr("not y"), "(not y)");
+        assert_eq!(repr("-x * 2"), "((-x) * 2)");
+        assert_eq!(repr("not a and b"), "((not a) and b)");
+    }
+
+    #[test]
+    fn function_definition() {
+        let defined = repr("def f(x, y):\n    return x + y");
+        assert_eq!(defined, "def f(x, y):\n    return (x + y)");
+        assert_eq!(repr(&defined), defined);
+    }
+
+    #[test]
+    fn if_else() {
+        let src = "if x > 1:\n    y = 1\nelse:\n    y = 2";
+        assert_eq!(repr(src), "if (x > 1):\n    y = 1\nelse:\n    y = 2");
+    }
+
+    #[test]
+    fn program_holds_every_statement() {
+        let program = parse("x = 1\ny = x + 1\nprint(y)", DEFAULT_DEPTH_LIMIT);
+        assert_eq!(program.unwrap().repr(), "x = 1\ny = (x + 1)\nprint(y)");
+    }
+
+    #[test]
+    fn nested_blocks_at_two_levels() {
+        let src = "if a:\n    if b:\n        x = 1\n    y = 2\nz = 3";
+        assert_eq!(
+            repr(src),
+            "if a:\n    if b:\n        x = 1\n    y = 2\nz = 3"
+        );
+    }
+
+    #[test]
+    fn calls() {
+        assert_eq!(repr("f()"), "f()");
+        assert_eq!(repr("f(1)"), "f(1)");
+        assert_eq!(repr("f(g(1), h())"), "f(g(1), h())");
+    }
+
+    #[test]
+    fn booleans() {
+        assert_eq!(repr("true"), "true");
+        assert_eq!(repr("false"), "false");
+        assert_eq!(repr("x = true and false"), "x = (true and false)");
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(repr("\"hello\""), "\"hello\"");
+        assert_eq!(repr("\"a\" + \"b\""), "(\"a\" + \"b\")");
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(repr("1 + 2.5"), "(1 + 2.5)");
+    }
+
+    #[test]
+    fn pow_is_right_associative() {
+        assert_eq!(repr("2 ** 3 ** 2"), "(2 ** (3 ** 2))");
+        assert_eq!(repr("2 * 3 ** 2"), "(2 * (3 ** 2))");
+        assert_eq!(eval("2 ** 3 ** 2"), "512");
+    }
+
+    #[test]
+    fn modulo() {
+        assert_eq!(repr("7 % 3"), "(7 % 3)");
+        assert_eq!(repr("1 + 7 % 3"), "(1 + (7 % 3))");
+        assert_eq!(eval("7 % 3"), "1");
+    }
+
+    #[test]
+    fn missing_operand_points_at_the_end() {
+        let Err(ParseError::UnexpectedToken { found, span, .. }) =
+            parse("1 +", DEFAULT_DEPTH_LIMIT)
+        else {
+            panic!("`1 +` should fail on its missing right side");
+        };
+        assert_eq!(found, Token::Eof);
+        assert_eq!(
+            span,
+            Span {
+                byte: 3,
+                line: 1,
+                col: 4
+            }
+        );
+    }
+
+    #[test]
+    fn input_ending_early_is_an_error() {
+        assert_eq!(repr("(1 + 2) * 3"), "((1 + 2) * 3)");
+        assert!(matches!(
+            parse("(1 + 2", DEFAULT_DEPTH_LIMIT),
+            Err(ParseError::UnclosedParen { .. })
+        ));
+    }
+
+    #[test]
+    fn unbalanced_parens() {
+        let Err(ParseError::UnclosedParen { open_span }) =
+            parse("x = 1 + (2 * (3 + 4)", DEFAULT_DEPTH_LIMIT)
+        else {
+            panic!("the first paren is never closed");
+        };
+        assert_eq!(open_span.col, 9);
+        let Err(ParseError::UnmatchedParen { span }) =
+            parse("x = (1 + 2))", DEFAULT_DEPTH_LIMIT)
+        else {
+            panic!("the last paren closes nothing");
+        };
+        assert_eq!(span.col, 12);
+        assert_eq!(repr("((1 + (2)) * (3))"), "((1 + 2) * 3)");
+    }
+
+    #[test]
+    fn walrus() {
+        assert_eq!(repr("x := 5"), "(x := 5)");
+        assert_eq!(repr("y = (x := 2) + 1"), "y = ((x := 2) + 1)");
+        assert_eq!(eval("(x := 2) + 1"), "3");
+        assert_eq!(eval("y = (x := 2) + 1\nx"), "2");
+    }
+
+    #[test]
+    fn pipes_become_calls() {
+        assert_eq!(repr("1 |> f |> g"), "g(f(1))");
+        assert_eq!(
+            parse("1 |> f |> g", DEFAULT_DEPTH_LIMIT).unwrap(),
+            parse("g(f(1))", DEFAULT_DEPTH_LIMIT).unwrap()
+        );
+    }
+
+    #[test]
+    fn compound_assignment() {
+        assert_eq!(repr("x += 2"), "x = (x + 2)");
+        assert_eq!(eval("mut x = 5\nx += 2\nx"), "7");
+        assert_eq!(eval("mut x = 5\nx -= 2\nx *= 4\nx /= 3\nx"), "4");
+    }
+
+    #[test]
+    fn peek_at_and_past_the_end() {
+        let tokens = Tokenizer::new("x".to_string()).tokenize_spanned();
+        let parser = Parser::new(tokens.unwrap());
+        assert_eq!(parser.peek_at(0), Some(&Token::Indent(0)));
+        assert_eq!(parser.peek(), Some(&Token::Identifier("x".to_string())));
+        assert_eq!(parser.peek_at(2), Some(&Token::Eof));
+        assert_eq!(parser.peek_at(3), None);
+        // Streaming holds only so many tokens ahead
+        let mut parser = Parser::from_lexer(Tokenizer::new("x".to_string()));
+        assert_eq!(parser.peek(), Some(&Token::Identifier("x".to_string())));
+        assert_eq!(parser.peek_at(LOOKAHEAD), None);
+        parser.step();
+        assert_eq!(parser.peek(), Some(&Token::Eof));
+        parser.step();
+        assert_eq!(parser.peek(), None);
+    }
+
+    #[test]
+    fn mutable_declarations() {
+        assert_eq!(repr("mut x = 1"), "mut x = 1");
+        assert_eq!(eval("mut x = 1\nx = 2\nx"), "2");
+        let err =
+            run_source("x = 1\nx = 2", &mut Environment::new()).unwrap_err();
+        assert!(matches!(
+            err,
+            RunError::Eval(EvalError::ImmutableAssignment(name)) if name == "x"
+        ));
+    }
+
+    #[test]
+    fn type_annotations() {
+        assert_eq!(repr("x: Int = 5"), "x: Int = 5");
+        let typed = "def add(a: Int, b: Int) -> Int:\n    return (a + b)";
+        assert_eq!(repr(typed), typed);
+        assert_eq!(eval("x: Int = 5\nx"), "5");
+    }
+
+    #[test]
+    fn streaming_parse_matches_the_vec_path() {
+        let src = "x = f(1, [2, 3][0]) * -(4 + y) ** 2 if a and not b else \
+                   {\"k\": (5, 6)}\nprint(x)";
+        let tokens = Tokenizer::new(src.to_string()).tokenize_spanned();
+        let collected = Parser::new(tokens.unwrap()).parse_program().unwrap();
+        let streamed = parse(src, DEFAULT_DEPTH_LIMIT).unwrap();
+        assert_eq!(streamed, collected);
+        assert_eq!(streamed.repr(), collected.repr());
+    }
+
+    #[test]
+    fn negative_literals() {
+        assert_eq!(repr("-5"), "-5");
+        assert_eq!(repr("3 - 5"), "(3 - 5)");
+        assert_eq!(repr("3 - -5"), "(3 - -5)");
+        assert_eq!(eval("3 - -5"), "8");
+    }
+
+    #[test]
+    fn list_literals() {
+        assert_eq!(repr("[]"), "[]");
+        assert_eq!(repr("[1, 2 + 3]"), "[1, (2 + 3)]");
+        assert_eq!(repr("[[1], [2, [3]]]"), "[[1], [2, [3]]]");
+        assert_eq!(eval("[[1], [2, 3]]"), "[[1], [2, 3]]");
+    }
+
+    #[test]
+    fn while_loop() {
+        assert_eq!(
+            repr("while x > 0:\n    x -= 1"),
+            "while (x > 0):\n    x = (x - 1)"
+        );
+        let src = "mut n = 3\nmut total = 0\nwhile n > 0:\n    \
+                   total += n\n    n -= 1\ntotal";
+        assert_eq!(eval(src), "6");
+        let mut env = Environment::with_loop_limit(100);
+        let err = run_source("while true:\n    1", &mut env).unwrap_err();
+        assert!(matches!(err, RunError::Eval(EvalError::LoopLimit(100))));
+    }
+
+    #[test]
+    fn for_loop() {
+        assert_eq!(
+            repr("for x in [1,2,3]:\n    s += x"),
+            "for x in [1, 2, 3]:\n    s = (s + x)"
+        );
+        let src = "mut s = 0\nfor x in [1,2,3]:\n    s += x\ns";
+        assert_eq!(eval(src), "6");
+    }
+
+    #[test]
+    fn break_and_continue() {
+        let src =
+            "mut n = 0\nwhile true:\n    n += 1\n    if n == 3:\n        \
+                   break\nn";
+        assert_eq!(eval(src), "3");
+        let src = "mut s = 0\nfor x in [1, 2, 3, 4]:\n    \
+                   if x % 2 == 0:\n        continue\n    s += x\ns";
+        assert_eq!(eval(src), "4");
+        assert!(matches!(
+            parse("break", DEFAULT_DEPTH_LIMIT),
+            Err(ParseError::InvalidTokenOrder(_))
+        ));
+    }
+
+    #[test]
+    fn unpacking_assignment() {
+        assert_eq!(repr("a, b = 1, 2 + 3"), "a, b = 1, (2 + 3)");
+        assert_eq!(eval("a, b = 1, 2\n[b, a]"), "[2, 1]");
+        assert!(matches!(
+            parse("a, b = 1, 2, 3", DEFAULT_DEPTH_LIMIT),
+            Err(ParseError::InvalidTokenOrder(_))
+        ));
+    }
+
+    #[test]
+    fn elif_chain() {
+        let src = "if a:\n    b\nelif c:\n    d\nelse:\n    e";
+        assert_eq!(repr(src), src);
+        let src =
+            "x = 2\nif x == 1:\n    1\nelif x == 2:\n    2\nelse:\n    3";
+        assert_eq!(eval(src), "2");
+    }
+
+    #[test]
+    fn match_literal_arms_and_a_wildcard() {
+        let src = "match x:\n    case 1:\n        \"one\"\n    \
+                   case 2:\n        \"two\"\n    case 3:\n        \
+                   \"three\"\n    case _:\n        \"many\"";
+        assert_eq!(repr(src), src);
+        for (x, matched) in [(1, "one"), (3, "three"), (9, "many")] {
+            assert_eq!(eval(&format!("x = {x}\n{src}")), matched);
+        }
+    }
+
+    #[test]
+    fn match_compares_numbers_by_value() {
+        let src = "match 1.0:\n    case 1:\n        print(1)\n    \
+                   case _:\n        print(2)\n";
+        assert_eq!(run(src).unwrap(), "1\n");
+        let src = "match \"1\":\n    case 1:\n        print(1)\n    \
+                   case \"1\":\n        print(2)\n";
+        assert_eq!(run(src).unwrap(), "2\n");
+    }
+
+    #[test]
+    fn precedence_steps() {
+        assert_eq!(Precedence::AddSub.higher(), Precedence::MulDiv);
+        assert_eq!(Precedence::Index.higher(), Precedence::Index);
+        assert_eq!(Precedence::Pow.lower(), Precedence::Prefix);
+        assert_eq!(Precedence::Lowest.lower(), Precedence::Lowest);
+        assert_eq!(repr("1 - 2 - 3"), "((1 - 2) - 3)");
+    }
+
+    #[test]
+    fn same_level_operators_are_left_associative() {
+        assert_eq!(repr("10 - 2 - 3"), "((10 - 2) - 3)");
+        assert_eq!(repr("16 / 4 / 2"), "((16 / 4) / 2)");
+        assert_eq!(repr("2 ** 3 ** 2"), "(2 ** (3 ** 2))");
+        assert_eq!(eval("10 - 2 - 3 == 5"), "true");
+        assert_eq!(eval("16 / 4 / 2 == 2"), "true");
+    }
+
+    #[test]
+    fn positional_argument_after_a_named_one() {
+        assert_eq!(repr("f(1, b=2)"), "f(1, b = 2)");
+        assert!(matches!(
+            parse("f(a=1, 2)", DEFAULT_DEPTH_LIMIT),
+            Err(ParseError::InvalidTokenOrder(_))
+        ));
+    }
+
+    #[test]
+    fn thousand_nested_parens_are_a_clean_error() {
+        // The default limit is set for a main thread's stack, which is
+        // bigger than a test thread's
+        let parsed = std::thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn(|| {
+                let src =
+                    format!("x = {}1{}", "(".repeat(1000), ")".repeat(1000));
+                return parse(&src, DEFAULT_DEPTH_LIMIT).map(|_| ());
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert!(matches!(
+            parsed,
+            Err(ParseError::TooDeep {
+                limit: DEFAULT_DEPTH_LIMIT,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn parsing_stops_at_eof() {
+        for src in ["", "x", "x = 1", "x = 1\n", "if a:\n    b"] {
+            let mut tokens =
+                Tokenizer::new(src.to_string()).tokenize_spanned().unwrap();
+            let without_eof = parse(src, DEFAULT_DEPTH_LIMIT).unwrap();
+            assert_eq!(tokens.pop().map(|(_, tok)| tok), Some(Token::Eof));
+            // A token list without its Eof ends in the same place
+            let program = Parser::new(tokens).parse_program().unwrap();
+            assert_eq!(program.repr(), without_eof.repr(), "{src:?}");
+        }
+        let tokens = Tokenizer::new("x".to_string()).tokenize_spanned();
+        let mut parser = Parser::new(tokens.unwrap());
+        parser.parse_program().unwrap();
+        // Sitting on Eof, there's nothing more to parse or step past
+        for _ in 0..3 {
+            assert!(parser.parse_stmt(0).unwrap().is_none());
+            parser.step();
+            assert_eq!(parser.get_token(), Token::Eof);
+        }
+    }
+
+    #[test]
+    fn each_error_displays_its_context() {
+        let span = Span {
+            byte: 4,
+            line: 2,
+            col: 3,
+        };
+        let reason = || "x".to_string();
+        let errors = [
+            (ParseError::InvalidTypeData(reason()), "invalid literal: x"),
+            (
+                ParseError::InvalidIndentLevel(reason()),
+                "invalid indent level: x",
+            ),
+            (
+                ParseError::InvalidTokenOrder(reason()),
+                "invalid token order: x",
+            ),
+            (
+                ParseError::UnexpectedToken {
+                    expected: "Colon".to_string(),
+                    found: Token::RParen,
+                    span,
+                },
+                "expected Colon but found ) at line 2, column 3",
+            ),
+            (
+                ParseError::NotImplementedToken("|>".to_string()),
+                "|> is not supported yet",
+            ),
+            (
+                ParseError::InvalidBlockStart(reason()),
+                "invalid block start: x",
+            ),
+            (
+                ParseError::UnclosedParen { open_span: span },
+                "paren opened at line 2, column 3 is never closed",
+            ),
+            (
+                ParseError::UnmatchedParen { span },
+                "closing paren at line 2, column 3 has no opening paren",
+            ),
+            (
+                ParseError::TooDeep { limit: 8, span },
+                "nested more than 8 deep at line 2, column 3",
+            ),
+            (
+                ParseError::TooLong { limit: 8, span },
+                "more than 8 operators in a statement at line 2, column 3",
+            ),
+            (
+                ParseError::Tokenize(TokenizerError::UnterminatedString(4)),
+                "unterminated string literal starting at offset 4",
+            ),
+        ];
+        for (error, message) in errors {
+            assert_eq!(error.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn ternary_binds_looser_than_arithmetic() {
+        assert_eq!(repr("x = 1 + 2 if a else 3"), "x = ((1 + 2) if a else 3)");
+        let src = "mut flag = true\nx = (1 if flag else 2) + 3\nprint(x)\n\
+                   flag = false\nprint(1 if flag else 2)\n";
+        assert_eq!(run(src).unwrap(), "4\n2\n");
+    }
+
+    #[test]
+    fn blank_line_inside_if_keeps_block() {
+        let src = "if 0:\n    print(1)\n\n    print(2)\nprint(3)\n";
+        assert_eq!(run(src).unwrap(), "3\n");
+    }
+
+    #[test]
+    fn comment_line_inside_if_keeps_block() {
+        let src = "if 0:\n    print(1)\n    # note\n    print(2)\n";
+        assert_eq!(run(src).unwrap(), "");
+        let src = "if 0:\n    print(1)\n# note\n    print(2)\n";
+        assert_eq!(run(src).unwrap(), "");
+    }
+
+    #[test]
+    fn blank_and_comment_lines_inside_def() {
+        let src = "def f():\n    a = 1\n\n    # note\n\n    return a\n\
+                   print(f())\n";
+        assert_eq!(run(src).unwrap(), "1\n");
+    }
+
+    #[test]
+    fn blank_and_comment_lines_inside_while() {
+        let src = "mut x = 0\nwhile x < 2:\n    x += 1\n\n    # note\n    \
+                   print(x)\nprint(10)\n";
+        assert_eq!(run(src).unwrap(), "1\n2\n10\n");
+    }
+
+    #[test]
+    fn blank_lines_inside_nested_blocks() {
+        let src = "def f(x):\n    if x:\n        print(1)\n\n        \
+                   # note\n\n        print(2)\n\n    print(3)\n\nf(0)\n\
+                   f(1)\n";
+        assert_eq!(run(src).unwrap(), "3\n1\n2\n3\n");
+    }
+
+    #[test]
+    fn trailing_blank_lines_and_comments() {
+        let src = "if 1:\n    print(1)\n\n\n# done\n   \n";
+        assert_eq!(run(src).unwrap(), "1\n");
+    }
+
+    #[test]
+    fn long_chain_is_not_nesting() {
+        let src = chain(1000) + "print(x)\n";
+        assert_eq!(run(&src).unwrap(), "1000\n");
+    }
+
+    #[test]
+    fn nested_parens_are_too_deep() {
+        let src = format!("x = {}1{}\n", "(".repeat(100), ")".repeat(100));
+        let err = parse(&src, 50).unwrap_err();
+        assert!(matches!(err, ParseError::TooDeep { limit: 50, .. }));
+    }
+
+    #[test]
+    fn chain_past_max_operators_is_too_long() {
+        assert!(parse(&chain(MAX_OPERATORS + 1), 256).is_ok());
+        let err = parse(&chain(MAX_OPERATORS + 2), 256).unwrap_err();
+        assert!(matches!(err, ParseError::TooLong { .. }));
+    }
+
+    #[test]
+    fn operators_are_counted_per_statement() {
+        let src = chain(MAX_OPERATORS) + &chain(MAX_OPERATORS);
+        assert!(parse(&src, 256).is_ok());
+    }
+
+    #[test]
+    fn match_on_a_negative_number() {
+        let src = "match -2:\n    case -1:\n        print(1)\n    \
+                   case -2:\n        print(2)\n    case _:\n        \
+                   print(3)\n";
+        assert_eq!(run(src).unwrap(), "2\n");
+        assert!(repr(src).contains("case -1:"), "{}", repr(src));
+    }
+
+    #[test]
+    fn only_numbers_are_negated_in_patterns() {
+        for src in [
+            "match 1:\n    case -x:\n        print(1)\n",
+            "match 1:\n    case -\"a\":\n        print(1)\n",
+        ] {
+            let err = parse(src, 256).unwrap_err();
+            assert!(
+                matches!(
+                    &err,
+                    ParseError::UnexpectedToken { expected, .. }
+                        if expected == "Number"
+                ),
+                "{src}: {err}"
+            );
+        }
     }
 }
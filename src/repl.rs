@@ -0,0 +1,89 @@
+use std::io::{self, BufRead, Write};
+
+use crate::eval::Value;
+use crate::interpreter::Interpreter;
+use crate::runner::RunError;
+
+/// Runs a chunk of source through the tokenizer, parser and evaluator,
+/// returning whatever the REPL should print for it. Bindings made by `src`
+/// stay in the interpreter for later lines
+pub fn eval_line(src: &str, interpreter: &mut Interpreter) -> String {
+    return match interpreter.run_source(src) {
+        Ok(Value::Unit) => String::new(),
+        Ok(result) => result.repr(),
+        Err(RunError::Tokenize(e)) => e.with_src(src).render(),
+        Err(e) => e.to_string(),
+    };
+}
+
+fn prompt(out: &mut dyn Write, text: &str) {
+    let _ = write!(out, "{text}");
+    let _ = out.flush();
+}
+
+/// Reads lines from stdin and evaluates them until EOF
+pub fn run_repl() {
+    run_repl_with(io::stdin().lock(), &mut Interpreter::default());
+}
+
+/// Reads lines from `input` and evaluates them until it runs out. A line
+/// ending in a colon opens a block, which is read until the next blank
+/// line. Prompts and results are written to the interpreter's output, as
+/// anything the program prints is
+pub fn run_repl_with(input: impl BufRead, interpreter: &mut Interpreter) {
+    let mut lines = input.lines();
+    loop {
+        prompt(interpreter.output(), ">>> ");
+        let Some(Ok(mut src)) = lines.next() else {
+            let _ = writeln!(interpreter.output());
+            return;
+        };
+        if src.trim_end().ends_with(':') {
+            loop {
+                prompt(interpreter.output(), "... ");
+                match lines.next() {
+                    Some(Ok(line)) if !line.trim().is_empty() => {
+                        src.push('\n');
+                        src.push_str(&line);
+                    }
+                    _ => break,
+                }
+            }
+        }
+        if src.trim().is_empty() {
+            continue;
+        }
+        let output = eval_line(&src, interpreter);
+        if !output.is_empty() {
+            let _ = writeln!(interpreter.output(), "{output}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn eval_line_keeps_bindings() {
+        let (mut interpreter, _) = testing::interpreter();
+        assert_eq!(eval_line("1 + 2", &mut interpreter), "3");
+        let lex_error = eval_line("x @ 5", &mut interpreter);
+        assert!(lex_error.ends_with("1 | x @ 5\n  |   ^"), "{lex_error}");
+        assert_eq!(eval_line("x = 2", &mut interpreter), "");
+        assert_eq!(eval_line("x * 5", &mut interpreter), "10");
+        assert_eq!(
+            eval_line("(1", &mut interpreter),
+            "paren opened at line 1, column 1 is never closed"
+        );
+    }
+
+    #[test]
+    fn repl_reads_blocks_until_a_blank_line() {
+        let (mut interpreter, output) = testing::interpreter();
+        let input = "x = 1\nif x:\n    print(\"yes\")\n\nx + 1\n";
+        run_repl_with(input.as_bytes(), &mut interpreter);
+        assert_eq!(output.contents(), ">>> >>> ... ... yes\n>>> 2\n>>> \n");
+    }
+}
@@ -0,0 +1,155 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::ast::Node;
+use crate::eval::{Environment, EvalError, Value};
+use crate::interpreter::Interpreter;
+use crate::parser::{ParseError, Parser};
+use crate::token::{Tokenizer, TokenizerError};
+
+/// Anything that can go wrong between reading a source and evaluating it
+#[derive(Debug)]
+pub enum RunError {
+    Io(io::Error),
+    Tokenize(TokenizerError),
+    Parse(ParseError),
+    Eval(EvalError),
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            RunError::Io(e) => write!(f, "{e}"),
+            RunError::Tokenize(e) => write!(f, "{e}"),
+            RunError::Parse(e) => write!(f, "{e}"),
+            RunError::Eval(e) => write!(f, "{e}"),
+        };
+    }
+}
+
+impl std::error::Error for RunError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        return match self {
+            RunError::Io(e) => Some(e),
+            RunError::Tokenize(e) => Some(e),
+            RunError::Parse(e) => Some(e),
+            RunError::Eval(e) => Some(e),
+        };
+    }
+}
+
+impl From<io::Error> for RunError {
+    fn from(e: io::Error) -> Self {
+        return RunError::Io(e);
+    }
+}
+
+impl From<TokenizerError> for RunError {
+    fn from(e: TokenizerError) -> Self {
+        return RunError::Tokenize(e);
+    }
+}
+
+impl From<ParseError> for RunError {
+    fn from(e: ParseError) -> Self {
+        return RunError::Parse(e);
+    }
+}
+
+impl From<EvalError> for RunError {
+    fn from(e: EvalError) -> Self {
+        return RunError::Eval(e);
+    }
+}
+
+/// Tokenizes, parses and evaluates a whole program against `env`,
+/// returning the result of its last statement
+pub fn run_source(
+    src: &str,
+    env: &mut Environment,
+) -> Result<Value, RunError> {
+    let tokens = Tokenizer::new(src.to_string()).tokenize_spanned()?;
+    let program = Parser::new(tokens).parse_program()?;
+    return Ok(program.eval(env)?);
+}
+
+/// Reads a source file from disk and runs every statement in it, printing
+/// to stdout
+pub fn run_file(path: &Path) -> Result<(), RunError> {
+    return run_file_with(path, &mut Interpreter::default());
+}
+
+/// Like run_file, but runs with `interpreter`, which is where anything it
+/// prints goes
+pub fn run_file_with(
+    path: &Path,
+    interpreter: &mut Interpreter,
+) -> Result<(), RunError> {
+    let src = fs::read_to_string(path)?;
+    interpreter.run_source(&src)?;
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    /// Writes `src` to a file in the temp dir named for the test using it
+    fn write_source(name: &str, src: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir()
+            .join(format!("funpy-{}-{name}.funpy", std::process::id()));
+        fs::write(&path, src).expect("temp dir is writable");
+        return path;
+    }
+
+    #[test]
+    fn runs_every_statement_in_a_file() {
+        let path = write_source("two", "x = 40\nprint(x + 2)\n");
+        let (mut interpreter, output) = testing::interpreter();
+        let ran = run_file_with(&path, &mut interpreter);
+        fs::remove_file(&path).unwrap();
+        ran.unwrap();
+        assert_eq!(output.contents(), "42\n");
+    }
+
+    #[test]
+    fn run_file_reports_every_stage() {
+        let path = write_source("bad", "x = (1\n");
+        let ran = run_file(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(ran, Err(RunError::Parse(_))));
+        let missing = std::env::temp_dir().join("funpy-missing.funpy");
+        assert!(matches!(run_file(&missing), Err(RunError::Io(_))));
+        let path = write_source("lex", "x @ 1\n");
+        let ran = run_file(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(ran, Err(RunError::Tokenize(_))));
+        let path = write_source("zero", "1 / 0\n");
+        let ran = run_file(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(ran, Err(RunError::Eval(_))));
+    }
+
+    #[test]
+    fn each_stage_converts_to_its_variant() {
+        let mut env = Environment::new();
+        let lexed = run_source("x = 1 @ 2", &mut env);
+        assert!(matches!(
+            lexed,
+            Err(RunError::Tokenize(TokenizerError::InvalidChar('@', 6)))
+        ));
+        let parsed = run_source("x = (1", &mut env);
+        assert!(matches!(
+            parsed,
+            Err(RunError::Parse(ParseError::UnclosedParen { .. }))
+        ));
+        let error = RunError::from(TokenizerError::UnterminatedString(0));
+        assert!(matches!(error, RunError::Tokenize(_)));
+        let error = RunError::from(ParseError::InvalidTypeData("x".into()));
+        assert!(matches!(error, RunError::Parse(_)));
+        assert_eq!(error.to_string(), "invalid literal: x");
+    }
+}
@@ -0,0 +1,44 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::eval::Limits;
+use crate::interpreter::Interpreter;
+use crate::runner::RunError;
+
+/// An output that can still be read after an Interpreter has taken it, so
+/// tests can check what a program printed
+#[derive(Clone, Default)]
+pub struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl SharedOutput {
+    pub fn contents(&self) -> String {
+        return String::from_utf8(self.0.borrow().clone())
+            .expect("programs only print UTF-8");
+    }
+}
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        return Ok(buf.len());
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        return Ok(());
+    }
+}
+
+/// An interpreter with the default limits whose output is captured
+pub fn interpreter() -> (Interpreter, SharedOutput) {
+    let output = SharedOutput::default();
+    let interpreter =
+        Interpreter::new(Box::new(output.clone()), Limits::default());
+    return (interpreter, output);
+}
+
+/// Runs `src` in a fresh interpreter, giving back what it printed
+pub fn run(src: &str) -> Result<String, RunError> {
+    let (mut interpreter, output) = interpreter();
+    interpreter.run_source(src)?;
+    return Ok(output.contents());
+}
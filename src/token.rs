@@ -1,5 +1,13 @@
-use std::fmt::{format, Display};
+use std::collections::VecDeque;
+use std::fmt::{Debug, Display};
+use std::iter::Peekable;
+use std::num::{ParseFloatError, ParseIntError};
+use std::ops::Range;
+use std::sync::Arc;
 
+/// The one token type shared by the Tokenizer and the Parser. Literal
+/// variants carry their text, so the parser matches on variants directly
+/// and there is no separate token-kind enum to keep in sync.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Token {
     Add,
@@ -8,8 +16,11 @@ pub enum Token {
     SubEq,
     Mul,
     MulEq,
+    Pow,
     Div,
     DivEq,
+    Mod,
+    ModEq,
 
     Eq,
     NotEq,
@@ -20,10 +31,17 @@ pub enum Token {
 
     Or,
     And,
+    Not,
     Dot,
 
     Int(String),
+    Float(String),
+    Str(StrLiteral),
+    Char(char),
     Identifier(String),
+    True,
+    False,
+    None,
 
     LParen,
     RParen,
@@ -45,16 +63,24 @@ pub enum Token {
     For,
     Return,
     Def,
+    Mut,
     Walrus,
     ReverseWalrus,
     Colon,
+    Semicolon,
 
     Indent(usize),
     Eof,
 
     Newline,
     If,
+    Elif,
     Else,
+    While,
+    Break,
+    Continue,
+    Match,
+    Case,
     In,
     Range,
     Struct,
@@ -67,6 +93,8 @@ pub enum Token {
 
 impl Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Room for a char literal's text, the one token that owns no str
+        let mut char_buf = [0; 4];
         let s = match self {
             Self::Add => "+",
             Self::AddEq => "+=",
@@ -74,8 +102,11 @@ impl Display for Token {
             Self::SubEq => "-=",
             Self::Mul => "*",
             Self::MulEq => "*=",
+            Self::Pow => "**",
             Self::Div => "/",
             Self::DivEq => "/=",
+            Self::Mod => "%",
+            Self::ModEq => "%=",
 
             Self::Eq => "==",
             Self::NotEq => "!=",
@@ -86,10 +117,17 @@ impl Display for Token {
 
             Self::Or => "or",
             Self::And => "and",
+            Self::Not => "not",
             Self::Dot => ".",
 
             Self::Int(i) =>i.as_str(),
+            Self::Float(f) => f.as_str(),
+            Self::Str(s) => s.as_str(),
+            Self::Char(ch) => ch.encode_utf8(&mut char_buf),
             Self::Identifier(ident) => ident.as_str(),
+            Self::True => "true",
+            Self::False => "false",
+            Self::None => "none",
 
             Self::LParen => "(",
             Self::RParen => ")",
@@ -110,16 +148,24 @@ impl Display for Token {
             Self::For => "for",
             Self::Return => "return",
             Self::Def => "def",
+            Self::Mut => "mut",
             Self::Walrus => ":=",
             Self::ReverseWalrus => "=:",
             Self::Colon => ":",
+            Self::Semicolon => ";",
 
             Self::Indent(_) => ">>>>",
             Self::Eof => "[EOF]",
 
             Self::Newline => "\n",
             Self::If => "if",
+            Self::Elif => "elif",
             Self::Else => "else",
+            Self::While => "while",
+            Self::Break => "break",
+            Self::Continue => "continue",
+            Self::Match => "match",
+            Self::Case => "case",
             Self::In => "in",
             Self::Range => "range",
             Self::Struct => "struct",
@@ -130,20 +176,365 @@ impl Display for Token {
             Self::Self_ => "self",
 
         };
-        write!(f, "{s}");
-        return Ok(());
+        return write!(f, "{s}");
+    }
+}
+
+impl Token {
+    /// Words the tokenizer reads as something other than an identifier
+    pub fn is_keyword(&self) -> bool {
+        // Keywords display as their spelling, so asking the tokenizer keeps
+        // this in step with get_keyword
+        let keyword = Tokenizer::get_keyword(&self.to_string());
+        return keyword.as_ref() == Some(self);
+    }
+    /// Tokens that are a value in their own right
+    pub fn is_literal(&self) -> bool {
+        return matches!(
+            self,
+            Token::Int(_)
+                | Token::Float(_)
+                | Token::Str(_)
+                | Token::Char(_)
+                | Token::True
+                | Token::False
+                | Token::None
+        );
+    }
+    /// Arithmetic, comparison, logical, pipe and assignment operators
+    pub fn is_operator(&self) -> bool {
+        return matches!(
+            self,
+            Token::Add
+                | Token::AddEq
+                | Token::Sub
+                | Token::SubEq
+                | Token::Mul
+                | Token::MulEq
+                | Token::Pow
+                | Token::Div
+                | Token::DivEq
+                | Token::Mod
+                | Token::ModEq
+                | Token::Eq
+                | Token::NotEq
+                | Token::Gt
+                | Token::GtEq
+                | Token::Lt
+                | Token::LtEq
+                | Token::Or
+                | Token::And
+                | Token::Not
+                | Token::Bang
+                | Token::Pipe
+                | Token::PipeMethod
+                | Token::PipeErr
+                | Token::Assignment
+                | Token::Walrus
+                | Token::ReverseWalrus
+        );
+    }
+}
+
+/// The text of a string literal. Most have no escapes, so their text is
+/// just a range of the source, which is shared rather than copied. Only
+/// one with escapes has text of its own. Arc rather than Rc, so tokens,
+/// and the errors holding them, can still be sent between threads
+#[derive(Clone)]
+pub enum StrLiteral {
+    Borrowed(Arc<str>, Range<usize>),
+    Owned(String),
+}
+
+impl StrLiteral {
+    pub fn as_str(&self) -> &str {
+        return match self {
+            StrLiteral::Borrowed(src, range) => &src[range.clone()],
+            StrLiteral::Owned(s) => s,
+        };
+    }
+}
+
+// Compared, and shown, by text alone, so a borrowed literal is the same as
+// an owned one
+impl PartialEq for StrLiteral {
+    fn eq(&self, other: &Self) -> bool {
+        return self.as_str() == other.as_str();
+    }
+}
+
+impl Eq for StrLiteral {}
+
+impl Debug for StrLiteral {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "{:?}", self.as_str());
+    }
+}
+
+impl From<String> for StrLiteral {
+    fn from(s: String) -> Self {
+        return StrLiteral::Owned(s);
+    }
+}
+
+impl From<&str> for StrLiteral {
+    fn from(s: &str) -> Self {
+        return StrLiteral::Owned(s.to_string());
+    }
+}
+
+/// Where a token starts in the source. `line` and `col` are 1-based so they
+/// match what an editor shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub byte: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Default for Span {
+    fn default() -> Self {
+        return Span {
+            byte: 0,
+            line: 1,
+            col: 1,
+        };
     }
 }
 
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "line {}, column {}", self.line, self.col);
+    }
+}
+
+pub type SpannedToken = (Span, Token);
+
+/// Offsets count chars rather than bytes, since the tokenizer walks the
+/// source as chars, so they line up with editor columns on non-ASCII lines
 #[derive(Debug)]
 pub enum TokenizerError {
     InvalidChar(char, usize),
+    UnterminatedString(usize),
+    UnterminatedChar(usize),
+    // How many chars were between the quotes, and where the literal starts
+    InvalidCharLiteral(usize, usize),
+    InvalidEscape(char, usize),
+    InvalidNumericLiteral(String, usize),
+    InvalidDigitSeparator(String, usize),
+    MixedIndentation(usize),
+    InvalidIndentation(usize, usize),
 }
+
+impl Display for TokenizerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            Self::InvalidChar(ch, ix) => {
+                write!(f, "invalid character {:?} at offset {ix}", ch)
+            }
+            Self::UnterminatedString(ix) => write!(
+                f,
+                "unterminated string literal starting at offset {ix}"
+            ),
+            Self::UnterminatedChar(ix) => write!(
+                f,
+                "unterminated char literal starting at offset {ix}"
+            ),
+            Self::InvalidCharLiteral(len, ix) => write!(
+                f,
+                "char literal at offset {ix} holds {len} characters, not one"
+            ),
+            Self::InvalidEscape(ch, ix) => {
+                write!(f, "invalid escape sequence \\{ch} at offset {ix}")
+            }
+            Self::InvalidNumericLiteral(literal, ix) => {
+                write!(f, "invalid numeric literal {literal} at offset {ix}")
+            }
+            Self::InvalidDigitSeparator(literal, ix) => write!(
+                f,
+                "numeric literal {literal} at offset {ix} has an underscore \
+                 that isn't between two digits"
+            ),
+            Self::MixedIndentation(ix) => write!(
+                f,
+                "indent is not all spaces or all tabs at offset {ix}"
+            ),
+            Self::InvalidIndentation(n_spaces, ix) => write!(
+                f,
+                "indent of {n_spaces} spaces at offset {ix} is not a multiple \
+                 of 4"
+            ),
+        };
+    }
+}
+
+impl std::error::Error for TokenizerError {}
+
+impl TokenizerError {
+    /// The char offset into the source the error was raised at
+    pub fn offset(&self) -> usize {
+        return match self {
+            Self::InvalidChar(_, ix)
+            | Self::UnterminatedString(ix)
+            | Self::UnterminatedChar(ix)
+            | Self::InvalidCharLiteral(_, ix)
+            | Self::InvalidEscape(_, ix)
+            | Self::InvalidNumericLiteral(_, ix)
+            | Self::InvalidDigitSeparator(_, ix)
+            | Self::MixedIndentation(ix)
+            | Self::InvalidIndentation(_, ix) => *ix,
+        };
+    }
+
+    /// Pairs the error with the source it came from so it can be rendered
+    /// in context
+    pub fn with_src<'src>(
+        &self,
+        src: &'src str,
+    ) -> WithSrcErr<'src, '_, TokenizerError> {
+        return WithSrcErr { src, err: self };
+    }
+}
+
+/// Whether `ch` starts a new line, given the char after it. A `\r` on its
+/// own ends a line, but in `\r\n` only the `\n` counts
+fn breaks_line(ch: char, next: Option<char>) -> bool {
+    return ch == '\n' || (ch == '\r' && next != Some('\n'));
+}
+
+/// How many lines `src` ends, by the same rules as breaks_line
+fn count_line_breaks(src: &str) -> usize {
+    let mut chars = src.chars().peekable();
+    let mut breaks = 0;
+    while let Some(ch) = chars.next() {
+        if breaks_line(ch, chars.peek().copied()) {
+            breaks += 1;
+        }
+    }
+    return breaks;
+}
+
+/// The radix a `0x`, `0o` or `0b` prefix at the start of `literal` means
+fn radix_prefix(literal: &str) -> Option<u32> {
+    return match literal.get(..2) {
+        Some("0x" | "0X") => Some(16),
+        Some("0o" | "0O") => Some(8),
+        Some("0b" | "0B") => Some(2),
+        _ => None,
+    };
+}
+
+/// Whether every underscore in a numeric literal sits between two digits,
+/// or straight after a radix prefix as in `0x_FF`
+fn separators_ok(literal: &str) -> bool {
+    let unsigned = literal.strip_prefix('-').unwrap_or(literal);
+    let prefixed = radix_prefix(unsigned).is_some();
+    // The radix's own digits are checked when the literal is parsed
+    let is_digit = |ch: &char| {
+        if prefixed {
+            return ch.is_ascii_hexdigit();
+        }
+        return ch.is_ascii_digit();
+    };
+    let chars: Vec<char> = unsigned.chars().collect();
+    return chars.iter().enumerate().all(|(ix, ch)| {
+        if *ch != '_' {
+            return true;
+        }
+        let after_prefix = prefixed && ix == 2;
+        let before = ix > 0 && (after_prefix || is_digit(&chars[ix - 1]));
+        return before && chars.get(ix + 1).is_some_and(is_digit);
+    });
+}
+
+/// The value of an int literal's text, which may have a `0x`, `0o` or `0b`
+/// radix prefix after its sign and underscores between its digits, as in
+/// `1_000` or `0xFF_FF`
+pub fn int_literal_value(literal: &str) -> Result<i64, ParseIntError> {
+    let (sign, unsigned) = match literal.strip_prefix('-') {
+        Some(unsigned) => ("-", unsigned),
+        None => ("", literal),
+    };
+    let (radix, digits) = match radix_prefix(unsigned) {
+        Some(radix) => (radix, &unsigned[2..]),
+        None => (10, unsigned),
+    };
+    let mut digits = digits.to_string();
+    // Left in, a misplaced underscore fails as an invalid digit
+    if separators_ok(literal) {
+        digits.retain(|ch| ch != '_');
+    }
+    return i64::from_str_radix(&format!("{sign}{digits}"), radix);
+}
+
+/// The value of a float literal's text, which may have underscores between
+/// its digits
+pub fn float_literal_value(literal: &str) -> Result<f64, ParseFloatError> {
+    if !separators_ok(literal) {
+        // Left in, the underscores make the literal fail to parse
+        return literal.parse::<f64>();
+    }
+    return literal.replace('_', "").parse::<f64>();
+}
+
+/// Whitespace that only separates tokens, which is any but line endings
+fn is_blank(ch: char) -> bool {
+    return ch.is_whitespace() && ch != '\n' && ch != '\r';
+}
+
+/// An error alongside the source it was raised in
+pub struct WithSrcErr<'src, 'err, E> {
+    src: &'src str,
+    err: &'err E,
+}
+
+impl WithSrcErr<'_, '_, TokenizerError> {
+    /// Renders the error rustc-style, with the offending line and a caret
+    /// under the char the error points at
+    pub fn render(&self) -> String {
+        let offset = self.err.offset();
+        let mut line_start = 0;
+        let mut line_no = 1;
+        let mut chars = self.src.chars().enumerate().peekable();
+        while let Some((ix, ch)) = chars.next() {
+            if ix >= offset {
+                break;
+            }
+            if breaks_line(ch, chars.peek().map(|(_, next)| *next)) {
+                line_start = ix + 1;
+                line_no += 1;
+            }
+        }
+        let line: String = self
+            .src
+            .chars()
+            .skip(line_start)
+            .take_while(|ch| *ch != '\n' && *ch != '\r')
+            .collect();
+        let col = offset - line_start;
+        let gutter = " ".repeat(line_no.to_string().len());
+        return format!(
+            "error: {}\n{gutter}--> {line_no}:{}\n{gutter} |\n\
+             {line_no} | {line}\n{gutter} | {}^",
+            self.err,
+            col + 1,
+            " ".repeat(col)
+        );
+    }
+}
+
 pub struct Tokenizer {
     src: Vec<char>,
+    // The source again, for plain string literals to borrow from
+    text: Arc<str>,
+    // span.byte at the start of text, which new_at starts past 0
+    text_offset: usize,
     src_len: usize,
     l: usize,
     r: usize,
+    // Position of src[span_ix], advanced lazily by span_at
+    span: Span,
+    span_ix: usize,
 }
 
 impl Tokenizer {
@@ -151,205 +542,456 @@ impl Tokenizer {
         let chars: Vec<char> = src.chars().collect();
         return Tokenizer {
             src: chars.clone(),
+            text: Arc::from(src),
+            text_offset: 0,
             src_len: chars.len(),
             l: 0,
             r: 0,
+            span: Span::default(),
+            span_ix: 0,
+        };
+    }
+    /// Lexes `src` from `byte` on, for re-lexing just the part of a source
+    /// that changed. Spans are still those of the whole source, with `line`
+    /// the 1-based line `byte` is on, but error offsets are counted from
+    /// `byte`. Panics if `byte` isn't on a char boundary
+    pub fn new_at(src: &str, byte: usize, line: usize) -> Self {
+        let line_start =
+            src[..byte].rfind(['\n', '\r']).map_or(0, |nl| nl + 1);
+        let mut tokenizer = Tokenizer::new(src[byte..].to_string());
+        tokenizer.span = Span {
+            byte,
+            line,
+            col: src[line_start..byte].chars().count() + 1,
         };
+        tokenizer.text_offset = byte;
+        return tokenizer;
     }
 
     pub fn tokenize(&mut self) -> Result<Vec<Token>, TokenizerError> {
+        return Ok(Self::tokens_only(self.tokenize_spanned()?));
+    }
+
+    /// Drops the spans for consumers that only care about the tokens
+    pub fn tokens_only(tokens: Vec<SpannedToken>) -> Vec<Token> {
+        return tokens.into_iter().map(|(_, tok)| tok).collect();
+    }
+
+    pub fn tokenize_spanned(
+        &mut self,
+    ) -> Result<Vec<SpannedToken>, TokenizerError> {
+        let (tokens, mut errors) = self.tokenize_inner(false);
+        if errors.is_empty() {
+            return Ok(tokens);
+        }
+        return Err(errors.remove(0));
+    }
+
+    /// Like tokenize_spanned, but on an error records it, skips the char it
+    /// was raised at and carries on, so every error in src is reported at
+    /// once alongside all the tokens that did lex
+    pub fn tokenize_lenient(
+        &mut self,
+    ) -> (Vec<SpannedToken>, Vec<TokenizerError>) {
+        return self.tokenize_inner(true);
+    }
+
+    /// Re-lexes `src` after an edit replaced the bytes `edit` of the source
+    /// `tokens` were lexed from, keeping the tokens the edit couldn't have
+    /// changed. Whether a line break becomes an Indent(0) depends on the
+    /// line after it, so the lines from the one above the edit through the
+    /// last one it touches are lexed again, and the tokens after them are
+    /// shifted to where they now sit. Error offsets are counted from the
+    /// start of the re-lexed lines
+    pub fn relex(
+        tokens: &[SpannedToken],
+        edit: Range<usize>,
+        src: &str,
+    ) -> Result<Vec<SpannedToken>, TokenizerError> {
+        let old_len = tokens.last().map_or(0, |(span, _)| span.byte);
+        let edit_end = edit.end + src.len() - old_len;
+        // The start of the line above the one the edit starts on
+        let line_start =
+            src[..edit.start].rfind(['\n', '\r']).map_or(0, |nl| nl + 1);
+        let above = src[..line_start]
+            .strip_suffix("\r\n")
+            .or_else(|| src[..line_start].strip_suffix(['\n', '\r']))
+            .unwrap_or("");
+        let start = above.rfind(['\n', '\r']).map_or(0, |nl| nl + 1);
+        // The line break ending the edited lines. A `\n` right at the end
+        // of the edit may be part of a `\r\n`, which is only whole again
+        // further on
+        let mut end =
+            src[edit_end..].find(['\n', '\r']).map(|nl| edit_end + nl);
+        if end == Some(edit_end) && src[edit_end..].starts_with('\n') {
+            end = src[edit_end + 1..]
+                .find(['\n', '\r'])
+                .map(|nl| edit_end + 1 + nl);
+        }
+
+        let prefix_len = tokens.partition_point(|(span, _)| span.byte < start);
+        let prefix = &tokens[..prefix_len];
+        let (from, line) = prefix
+            .last()
+            .map_or((0, 1), |(span, _)| (span.byte, span.line));
+        let line = line + count_line_breaks(&src[from..start]);
+        let mut relexed = Tokenizer::new_at(
+            &src[..end.unwrap_or(src.len())],
+            start,
+            line,
+        )
+        .tokenize_spanned()?;
+        // Only the first line of the source starts with a bare Indent(0)
+        if start > 0 {
+            relexed.remove(0);
+        }
+        let Some(end) = end else {
+            return Ok([prefix, &relexed].concat());
+        };
+
+        // The line break at `end` is now wherever the re-lexed tokens ended
+        let (end_span, _) = relexed.pop().expect("relexed ends in an Eof");
+        let old_end = end + old_len - src.len();
+        // The first line's Indent(0) shares the span of the token after it,
+        // so has to be skipped by index if it wasn't kept in the prefix
+        let rest = &tokens[prefix_len.max(1)..];
+        let suffix =
+            &rest[rest.partition_point(|(span, _)| span.byte < old_end)..];
+        // The line break may have been dropped, along with any blank lines
+        // after it, so its old line is counted back from the next token
+        let old_line = match suffix.first() {
+            Some((span, _)) => {
+                let after = &src[end..span.byte - old_end + end];
+                span.line - count_line_breaks(after)
+            }
+            None => end_span.line,
+        };
+        let suffix = suffix.iter().map(|(span, tok)| {
+            if span.byte == old_end {
+                return (end_span, tok.clone());
+            }
+            let span = Span {
+                byte: span.byte - old_end + end,
+                line: span.line - old_line + end_span.line,
+                col: span.col,
+            };
+            return (span, tok.clone());
+        });
+        let tokens = prefix.iter().cloned().chain(relexed).chain(suffix);
+        return Ok(tokens.collect());
+    }
+
+    fn tokenize_inner(
+        &mut self,
+        lenient: bool,
+    ) -> (Vec<SpannedToken>, Vec<TokenizerError>) {
         let mut tokens: Vec<Token> = Vec::new();
+        let mut spans: Vec<Span> = Vec::new();
+        let mut errors: Vec<TokenizerError> = Vec::new();
         while self.r < self.src_len {
-            let ch = self.src[self.r];
-            match ch {
-                ' ' => {
-                    while self.r < self.src_len && self.src[self.r] == ' ' {
-                        self.r += 1;
-                    }
-
-                    let n_spaces = self.r - self.l;
-                    if n_spaces >= 1 {
-                        if n_spaces % 4 == 0 {
-                            tokens.push(Token::Indent(n_spaces / 4));
-                        }
-                    }
-
-                    self.l = self.r;
+            // Whatever this iteration pushes starts at the current char
+            let span = self.span_at(self.r);
+            let start = self.r;
+            if let Err(e) = self.next_tokens(&mut tokens) {
+                errors.push(e);
+                if !lenient {
+                    break;
                 }
-                // Indent + Newlines
-                '\n' => tokens.push(self.get_char_op(Token::Newline)),
-                // Single Char Operators
-                '(' => tokens.push(self.get_char_op(Token::LParen)),
-                ')' => tokens.push(self.get_char_op(Token::RParen)),
-                '[' => tokens.push(self.get_char_op(Token::LSquare)),
-                ']' => tokens.push(self.get_char_op(Token::RSquare)),
-                '{' => tokens.push(self.get_char_op(Token::RSquare)),
-                '}' => tokens.push(self.get_char_op(Token::RSquare)),
-                ',' => tokens.push(self.get_char_op(Token::Comma)),
-                '.' => tokens.push(self.get_char_op(Token::Dot)),
-                '<' => {
-                    let token = if self.next_char_is('=') {
-                        self.get_long_op(Token::LtEq)
-                    } else {
-                        self.get_char_op(Token::Lt)
-                    };
-                    tokens.push(token);
-                }
-                '>' => {
-                    let token = if self.next_char_is('=') {
-                        self.get_long_op(Token::GtEq)
-                    } else {
-                        self.get_char_op(Token::Gt)
-                    };
-                    tokens.push(token);
-                }
-                '+' => {
-                    let token = if self.next_char_is('=') {
-                        self.get_long_op(Token::AddEq)
-                    } else {
-                        self.get_char_op(Token::Add)
-                    };
-                    tokens.push(token);
-                }
-                '-' => {
-                    let token = if self.next_char_is('=') {
-                        self.get_long_op(Token::SubEq)
-                    } else if self.next_char_is('>') {
-                        self.get_long_op(Token::Arrow)
-                    } else {
-                        self.get_char_op(Token::Sub)
-                    };
-                    tokens.push(token);
-                }
-                '*' => {
-                    let token = if self.next_char_is('=') {
-                        self.get_long_op(Token::MulEq)
-                    } else {
-                        self.get_char_op(Token::Mul)
-                    };
-                    tokens.push(token);
-                }
-                '/' => {
-                    let token = if self.next_char_is('=') {
-                        self.get_long_op(Token::DivEq)
-                    } else {
-                        self.get_char_op(Token::Div)
-                    };
-                    tokens.push(token);
-                }
-                '=' => {
-                    let token = match self.get_next_char() {
-                        Some('=') => self.get_long_op(Token::Eq),
-                        Some(':') => self.get_long_op(Token::ReverseWalrus),
-                        _ => self.get_char_op(Token::Assignment),
-                    };
-                    tokens.push(token);
-                }
-                ':' => {
-                    let token = if self.next_char_is('=') {
-                        self.get_long_op(Token::Walrus)
-                    } else {
-                        self.get_char_op(Token::Colon)
-                    };
-                    tokens.push(token);
-                }
-                '|' => {
-                    let token = match self.get_next_char() {
-                        Some('>') => self.get_long_op(Token::Pipe),
-                        Some('.') => self.get_long_op(Token::PipeMethod),
-                        Some('!') => self.get_long_op(Token::PipeErr),
-                        _ => self.get_char_op(Token::Bar),
-                    };
-                    tokens.push(token);
-                }
-                '!' => {
-                    let token = if self.next_char_is('=') {
-                        self.get_long_op(Token::NotEq)
-                    } else {
-                        self.get_char_op(Token::Bang)
-                    };
-                    tokens.push(token);
-                }
-                '0'..='9' => {
-                    tokens.push(self.get_numerical_literal());
-                }
-                'a'..='z' | 'A'..='Z' | '_' => {
-                    tokens.push(self.get_alpha_literal());
-                }
-                _ => {
-                    println!("{:?}", ch);
-                    return Err(TokenizerError::InvalidChar(ch, self.r));
+                if self.r == start {
+                    self.r += 1;
                 }
+                self.l = self.r;
             }
+            spans.resize(tokens.len(), span);
         }
         tokens.push(Token::Eof);
-        return Ok(Self::make_nice_indents(tokens));
-    }
-    pub fn make_nice_indents(tokens: Vec<Token>) -> Vec<Token> {
-        let mut nice_tokens: Vec<Token> = Vec::new();
-        nice_tokens.push(Token::Indent(0));
-        for (ix, tok) in tokens.iter().enumerate() {
-            match tok {
-                Token::Indent(_) => {
-                    if ix + 1 < tokens.len() {
-                        match tokens[ix + 1] {
-                            Token::Pipe | Token::PipeMethod => {}
-                            _ => {
-                                nice_tokens.push(tok.clone());
-                            }
-                        }
-                    }
-                }
-                Token::Newline => {
-                    if ix + 1 < tokens.len() {
-                        match tokens[ix + 1] {
-                            Token::Pipe | Token::PipeMethod => {}
-                            Token::Indent(_) => {}
-                            _ => {
-                                nice_tokens.push(Token::Indent(0));
-                            }
-                        }
-                    }
+        spans.resize(tokens.len(), self.span_at(self.src_len));
+        let tokens = spans.into_iter().zip(tokens).collect();
+        return (Self::make_nice_indents(tokens), errors);
+    }
+
+    /// Lexes whatever starts at the current char onto `tokens`
+    fn next_tokens(
+        &mut self,
+        tokens: &mut Vec<Token>,
+    ) -> Result<(), TokenizerError> {
+        let ch = self.src[self.r];
+        match ch {
+            ch if is_blank(ch) => {
+                // A tokenizer from new_at may start partway through a line,
+                // which span, still at the first char, shows
+                let at_line_start = match self.r {
+                    0 => self.span.col == 1,
+                    r => ['\n', '\r'].contains(&self.src[r - 1]),
+                };
+                while self.r < self.src_len && is_blank(self.src[self.r]) {
+                    self.r += 1;
                 }
-                _ => {
-                    nice_tokens.push(tok.clone());
+
+                // Only leading whitespace on a line with code is an
+                // indent, anything else just separates tokens
+                if at_line_start && !self.at_line_end() {
+                    tokens.push(self.get_indent()?);
                 }
+
+                self.l = self.r;
+            }
+            // Indent + Newlines
+            // Every line ending emits its own Newline, so blank lines are
+            // not collapsed here. make_nice_indents drops all but the last
+            // of a run, which becomes an Indent(0) unless the line after
+            // it is indented
+            '\n' => tokens.push(self.get_char_op(Token::Newline)),
+            // Windows line endings are one Newline, and a lone `\r` as
+            // old Macs write them is one too
+            '\r' if self.next_char_is('\n') => {
+                tokens.push(self.get_long_op(Token::Newline))
+            }
+            '\r' => tokens.push(self.get_char_op(Token::Newline)),
+            // Comments run to the end of the line and emit nothing
+            '#' => self.skip_comment(),
+            // Single Char Operators
+            '(' => tokens.push(self.get_char_op(Token::LParen)),
+            ')' => tokens.push(self.get_char_op(Token::RParen)),
+            '[' => tokens.push(self.get_char_op(Token::LSquare)),
+            ']' => tokens.push(self.get_char_op(Token::RSquare)),
+            '{' => tokens.push(self.get_char_op(Token::LBrace)),
+            '}' => tokens.push(self.get_char_op(Token::RBrace)),
+            ',' => tokens.push(self.get_char_op(Token::Comma)),
+            ';' => tokens.push(self.get_char_op(Token::Semicolon)),
+            '.' => tokens.push(self.get_char_op(Token::Dot)),
+            '<' => {
+                let token = if self.next_char_is('=') {
+                    self.get_long_op(Token::LtEq)
+                } else {
+                    self.get_char_op(Token::Lt)
+                };
+                tokens.push(token);
+            }
+            '>' => {
+                let token = if self.next_char_is('=') {
+                    self.get_long_op(Token::GtEq)
+                } else {
+                    self.get_char_op(Token::Gt)
+                };
+                tokens.push(token);
+            }
+            '+' => {
+                let token = if self.next_char_is('=') {
+                    self.get_long_op(Token::AddEq)
+                } else {
+                    self.get_char_op(Token::Add)
+                };
+                tokens.push(token);
+            }
+            '-' => {
+                let token = if self.next_char_is('=') {
+                    self.get_long_op(Token::SubEq)
+                } else if self.next_char_is('>') {
+                    self.get_long_op(Token::Arrow)
+                } else {
+                    self.get_char_op(Token::Sub)
+                };
+                tokens.push(token);
+            }
+            '*' => {
+                let token = if self.next_char_is('=') {
+                    self.get_long_op(Token::MulEq)
+                } else if self.next_char_is('*') {
+                    self.get_long_op(Token::Pow)
+                } else {
+                    self.get_char_op(Token::Mul)
+                };
+                tokens.push(token);
+            }
+            '/' => {
+                let token = if self.next_char_is('=') {
+                    self.get_long_op(Token::DivEq)
+                } else {
+                    self.get_char_op(Token::Div)
+                };
+                tokens.push(token);
+            }
+            '%' => {
+                let token = if self.next_char_is('=') {
+                    self.get_long_op(Token::ModEq)
+                } else {
+                    self.get_char_op(Token::Mod)
+                };
+                tokens.push(token);
+            }
+            '=' => {
+                let token = match self.get_next_char() {
+                    Some('=') => self.get_long_op(Token::Eq),
+                    Some(':') => self.get_long_op(Token::ReverseWalrus),
+                    _ => self.get_char_op(Token::Assignment),
+                };
+                tokens.push(token);
+            }
+            ':' => {
+                let token = if self.next_char_is('=') {
+                    self.get_long_op(Token::Walrus)
+                } else {
+                    self.get_char_op(Token::Colon)
+                };
+                tokens.push(token);
+            }
+            '|' => {
+                let token = match self.get_next_char() {
+                    Some('>') => self.get_long_op(Token::Pipe),
+                    Some('.') => self.get_long_op(Token::PipeMethod),
+                    Some('!') => self.get_long_op(Token::PipeErr),
+                    _ => self.get_char_op(Token::Bar),
+                };
+                tokens.push(token);
+            }
+            '!' => {
+                let token = if self.next_char_is('=') {
+                    self.get_long_op(Token::NotEq)
+                } else {
+                    self.get_char_op(Token::Bang)
+                };
+                tokens.push(token);
+            }
+            '"' => tokens.push(self.get_string_literal()?),
+            '\'' => tokens.push(self.get_char_literal()?),
+            '0'..='9' => {
+                tokens.push(self.get_numerical_literal()?);
+            }
+            'a'..='z' | 'A'..='Z' | '_' => {
+                tokens.push(self.get_alpha_literal());
+            }
+            _ => {
+                return Err(TokenizerError::InvalidChar(ch, self.r));
+            }
+        }
+        return Ok(());
+    }
+    pub fn make_nice_indents(tokens: Vec<SpannedToken>) -> Vec<SpannedToken> {
+        let mut nice_tokens: Vec<SpannedToken> = Vec::new();
+        // The first line's indent sits wherever the source starts
+        let start = tokens.first().map(|(span, _)| *span).unwrap_or_default();
+        nice_tokens.push((start, Token::Indent(0)));
+        for (ix, (span, tok)) in tokens.iter().enumerate() {
+            let next = tokens.get(ix + 1).map(|(_, next)| next);
+            if let Some(tok) = Self::nice_token(tok, next) {
+                nice_tokens.push((*span, tok));
             }
         }
         return nice_tokens;
     }
+    /// What make_nice_indents keeps of `tok`, given the token after it.
+    /// Indents in front of a pipe continue the line above, and a Newline
+    /// only stands for an Indent(0) when the next line has code and no
+    /// Indent. Blank and comment-only lines lex to nothing but their
+    /// Newline, so only the last line break before code counts and a
+    /// block carries on past them
+    fn nice_token(tok: &Token, next: Option<&Token>) -> Option<Token> {
+        return match (tok, next) {
+            (Token::Indent(_) | Token::Newline, None) => None,
+            (
+                Token::Indent(_) | Token::Newline,
+                Some(Token::Pipe | Token::PipeMethod),
+            ) => None,
+            (
+                Token::Newline,
+                Some(Token::Indent(_) | Token::Newline | Token::Eof),
+            ) => None,
+            (Token::Newline, _) => Some(Token::Indent(0)),
+            _ => Some(tok.clone()),
+        };
+    }
+    fn span_at(&mut self, ix: usize) -> Span {
+        while self.span_ix < ix {
+            let ch = self.src[self.span_ix];
+            self.span.byte += ch.len_utf8();
+            if breaks_line(ch, self.src.get(self.span_ix + 1).copied()) {
+                self.span.line += 1;
+                self.span.col = 1;
+            } else {
+                self.span.col += 1;
+            }
+            self.span_ix += 1;
+        }
+        return self.span;
+    }
+    pub fn at_line_end(&self) -> bool {
+        return self.r == self.src_len
+            || ['\n', '\r', '#'].contains(&self.src[self.r]);
+    }
+    /// Indents are either all spaces, four to a level, or all tabs, one to
+    /// a level
+    pub fn get_indent(&self) -> Result<Token, TokenizerError> {
+        let whitespace = &self.src[self.l..self.r];
+        if whitespace.iter().all(|ch| *ch == '\t') {
+            return Ok(Token::Indent(whitespace.len()));
+        }
+        // Anything else in an indent, like a form feed, is as bad as mixing
+        // tabs with spaces
+        if whitespace.iter().any(|ch| *ch != ' ') {
+            return Err(TokenizerError::MixedIndentation(self.l));
+        }
+        if !whitespace.len().is_multiple_of(4) {
+            return Err(TokenizerError::InvalidIndentation(
+                whitespace.len(),
+                self.l,
+            ));
+        }
+        return Ok(Token::Indent(whitespace.len() / 4));
+    }
+    pub fn skip_comment(&mut self) {
+        // Stop short of the newline so it still ends the line
+        while self.r < self.src_len
+            && !['\n', '\r'].contains(&self.src[self.r])
+        {
+            self.r += 1;
+        }
+        self.l = self.r;
+    }
     pub fn get_next_char(&self) -> Option<char> {
         if self.r + 1 < self.src_len {
             return Some(self.src[self.r + 1]);
         }
         return None;
     }
+    pub fn current_char_is(&self, ch: char) -> bool {
+        return self.r < self.src_len && self.src[self.r] == ch;
+    }
     pub fn next_char_is(&self, ch: char) -> bool {
         return self.r + 1 < self.src_len && self.src[self.r + 1] == ch;
     }
 
     pub fn get_alpha_literal(&mut self) -> Token {
         while self.r < self.src_len
-            && (('a'..='z').contains(&self.src[self.r])
-                || ('A'..='Z').contains(&self.src[self.r])
+            && (self.src[self.r].is_ascii_alphanumeric()
                 || '_' == self.src[self.r])
         {
             self.r += 1;
         }
-        let literal = self.src[self.l..self.r].iter().collect();
+        let literal: String = self.src[self.l..self.r].iter().collect();
         self.l = self.r;
         return Tokenizer::get_keyword(&literal)
             .unwrap_or(Token::Identifier(literal));
     }
-    pub fn get_keyword(literal: &String) -> Option<Token> {
-        let literal_str = literal.as_str();
-        return match literal_str {
+    pub fn get_keyword(literal: &str) -> Option<Token> {
+        return match literal {
             "for" => Some(Token::For),
             "def" => Some(Token::Def),
+            "mut" => Some(Token::Mut),
             "or" => Some(Token::Or),
             "and" => Some(Token::And),
+            "not" => Some(Token::Not),
+            "true" => Some(Token::True),
+            "false" => Some(Token::False),
+            "none" => Some(Token::None),
             "if" => Some(Token::If),
+            "elif" => Some(Token::Elif),
             "else" => Some(Token::Else),
+            "while" => Some(Token::While),
+            "break" => Some(Token::Break),
+            "continue" => Some(Token::Continue),
+            "match" => Some(Token::Match),
+            "case" => Some(Token::Case),
             "in" => Some(Token::In),
             "range" => Some(Token::Range),
             "return" => Some(Token::Return),
@@ -360,16 +1002,152 @@ impl Tokenizer {
             _ => None,
         };
     }
-    pub fn get_numerical_literal(&mut self) -> Token {
-        while self.r < self.src_len && ('0'..='9').contains(&self.src[self.r])
+    pub fn get_string_literal(&mut self) -> Result<Token, TokenizerError> {
+        let start = self.r;
+        // Consume opening quote
+        self.r += 1;
+        // Most strings have no escapes, so the run up to the first char
+        // that needs looking at is taken in one go
+        let plain_len = self.src[self.r..]
+            .iter()
+            .position(|ch| ['"', '\\', '\n', '\r'].contains(ch))
+            .unwrap_or(self.src_len - self.r);
+        let from = self.span_at(self.r).byte - self.text_offset;
+        let plain = &self.src[self.r..self.r + plain_len];
+        let to = from + plain.iter().map(|ch| ch.len_utf8()).sum::<usize>();
+        self.r += plain_len;
+        // With nothing to resolve, the literal is that run of the source
+        if self.current_char_is('"') {
+            let literal = StrLiteral::Borrowed(self.text.clone(), from..to);
+            return Ok(self.get_char_op(Token::Str(literal)));
+        }
+        let mut literal = self.text[from..to].to_string();
+        while self.r < self.src_len
+            && !['"', '\n', '\r'].contains(&self.src[self.r])
+        {
+            let ch = self.src[self.r];
+            if ch != '\\' {
+                literal.push(ch);
+                self.r += 1;
+                continue;
+            }
+            let Some(escaped) = self.get_escape('"')? else {
+                break;
+            };
+            literal.push(escaped);
+            self.r += 2;
+        }
+        if !self.current_char_is('"') {
+            return Err(TokenizerError::UnterminatedString(start));
+        }
+        // Consume closing quote
+        return Ok(self.get_char_op(Token::Str(literal.into())));
+    }
+    /// Lexes a char literal, which takes the same escapes as a string but
+    /// with `\'` in place of `\"`, and must hold exactly one char
+    pub fn get_char_literal(&mut self) -> Result<Token, TokenizerError> {
+        let start = self.r;
+        // Consume opening quote
+        self.r += 1;
+        let mut chars = Vec::new();
+        while self.r < self.src_len
+            && !['\'', '\n', '\r'].contains(&self.src[self.r])
+        {
+            let ch = self.src[self.r];
+            if ch != '\\' {
+                chars.push(ch);
+                self.r += 1;
+                continue;
+            }
+            let Some(escaped) = self.get_escape('\'')? else {
+                break;
+            };
+            chars.push(escaped);
+            self.r += 2;
+        }
+        if !self.current_char_is('\'') {
+            return Err(TokenizerError::UnterminatedChar(start));
+        }
+        let [ch] = chars[..] else {
+            let len = chars.len();
+            return Err(TokenizerError::InvalidCharLiteral(len, start));
+        };
+        // Consume closing quote
+        return Ok(self.get_char_op(Token::Char(ch)));
+    }
+    /// The char the escape sequence at the cursor stands for, in a literal
+    /// delimited by `quote`. None when the source ends after the backslash
+    fn get_escape(
+        &self,
+        quote: char,
+    ) -> Result<Option<char>, TokenizerError> {
+        return match self.get_next_char() {
+            Some('n') => Ok(Some('\n')),
+            Some('t') => Ok(Some('\t')),
+            Some('\\') => Ok(Some('\\')),
+            Some('0') => Ok(Some('\0')),
+            Some(ch) if ch == quote => Ok(Some(ch)),
+            Some(other) => Err(TokenizerError::InvalidEscape(other, self.r)),
+            None => Ok(None),
+        };
+    }
+    pub fn get_numerical_literal(&mut self) -> Result<Token, TokenizerError> {
+        let start = self.l;
+        self.skip_digits();
+        // Only a digit after the dot makes a float, so `1.foo` stays a
+        // method access
+        let mut is_float = self.current_char_is('.')
+            && self.get_next_char().is_some_and(|ch| ch.is_ascii_digit());
+        if is_float {
+            self.r += 1;
+            self.skip_digits();
+        }
+        // An exponent, also only with a digit after it, so a bare `1e` is
+        // left for the typo check below
+        if self.current_char_is('e') || self.current_char_is('E') {
+            let sign = self.next_char_is('+') || self.next_char_is('-');
+            let digit_at = self.r + 1 + usize::from(sign);
+            if self.src.get(digit_at).is_some_and(char::is_ascii_digit) {
+                is_float = true;
+                self.r = digit_at;
+                self.skip_digits();
+            }
+        }
+        // A number running straight into a name (`1abc`) is a typo, not
+        // two tokens. This also takes in the digits after a radix prefix,
+        // which int_literal_value checks
+        while self.r < self.src_len
+            && (self.src[self.r].is_ascii_alphanumeric()
+                || self.src[self.r] == '_')
         {
             self.r += 1;
         }
-        let literal = self.src[self.l..self.r]
-            .iter()
-            .collect();
+        let literal: String = self.src[self.l..self.r].iter().collect();
         self.l = self.r;
-        return Token::Int(literal);
+        if !separators_ok(&literal) {
+            return Err(TokenizerError::InvalidDigitSeparator(literal, start));
+        }
+        if is_float {
+            // Too big an exponent parses as infinity, which no literal means
+            if !float_literal_value(&literal).is_ok_and(f64::is_finite) {
+                return Err(TokenizerError::InvalidNumericLiteral(
+                    literal, start,
+                ));
+            }
+            return Ok(Token::Float(literal));
+        }
+        if int_literal_value(&literal).is_err() {
+            return Err(TokenizerError::InvalidNumericLiteral(literal, start));
+        }
+        return Ok(Token::Int(literal));
+    }
+    /// Steps over digits and the underscores that may separate them
+    fn skip_digits(&mut self) {
+        while self.r < self.src_len
+            && (self.src[self.r].is_ascii_digit() || self.src[self.r] == '_')
+        {
+            self.r += 1;
+        }
     }
     pub fn get_long_op(&mut self, tk: Token) -> Token {
         // Consume first char of operator
@@ -385,3 +1163,710 @@ impl Tokenizer {
         return tk;
     }
 }
+
+/// Lexes lazily, only as far as the consumer has asked, with the same
+/// indent clean up as tokenize_spanned. The end of input comes through as
+/// a single Eof before the stream runs dry, so a consumer can match on it
+/// like any other token. Stops after the first error, with no Eof
+pub struct TokenStream {
+    tokenizer: Tokenizer,
+    // Lexed but not yet cleaned up, kept a token ahead for nice_token
+    raw: VecDeque<SpannedToken>,
+    started: bool,
+    lexed_all: bool,
+}
+
+impl TokenStream {
+    pub fn new(tokenizer: Tokenizer) -> Self {
+        return TokenStream {
+            tokenizer,
+            raw: VecDeque::new(),
+            started: false,
+            lexed_all: false,
+        };
+    }
+    fn fill(&mut self) -> Result<(), TokenizerError> {
+        let t = &mut self.tokenizer;
+        while self.raw.len() < 2 && !self.lexed_all {
+            if t.r >= t.src_len {
+                self.raw.push_back((t.span_at(t.src_len), Token::Eof));
+                self.lexed_all = true;
+                break;
+            }
+            let span = t.span_at(t.r);
+            let mut tokens = Vec::new();
+            if let Err(e) = t.next_tokens(&mut tokens) {
+                // Nothing more comes after an error
+                self.raw.clear();
+                self.lexed_all = true;
+                return Err(e);
+            }
+            self.raw.extend(tokens.into_iter().map(|tok| (span, tok)));
+        }
+        return Ok(());
+    }
+}
+
+impl Iterator for TokenStream {
+    type Item = Result<SpannedToken, TokenizerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            let start = self.tokenizer.span_at(0);
+            return Some(Ok((start, Token::Indent(0))));
+        }
+        loop {
+            if let Err(e) = self.fill() {
+                return Some(Err(e));
+            }
+            let (span, tok) = self.raw.pop_front()?;
+            let next = self.raw.front().map(|(_, next)| next);
+            if let Some(tok) = Tokenizer::nice_token(&tok, next) {
+                return Some(Ok((span, tok)));
+            }
+        }
+    }
+}
+
+/// A TokenStream with one token of lookahead
+pub struct PeekableLexer {
+    stream: Peekable<TokenStream>,
+}
+
+impl PeekableLexer {
+    pub fn new(tokenizer: Tokenizer) -> Self {
+        return PeekableLexer {
+            stream: TokenStream::new(tokenizer).peekable(),
+        };
+    }
+    /// The next token, without consuming it
+    pub fn peek(&mut self) -> Option<&Result<SpannedToken, TokenizerError>> {
+        return self.stream.peek();
+    }
+    /// Consumes and returns the next token
+    pub fn advance(&mut self) -> Option<Result<SpannedToken, TokenizerError>> {
+        return self.stream.next();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::run;
+
+    fn lex(src: &str) -> Vec<SpannedToken> {
+        return Tokenizer::new(src.to_string())
+            .tokenize_spanned()
+            .expect("test source lexes");
+    }
+
+    /// The tokens of `src` between its leading Indent and the Eof
+    fn tokens(src: &str) -> Vec<Token> {
+        let mut tokens = Tokenizer::tokens_only(lex(src));
+        assert_eq!(tokens.pop(), Some(Token::Eof));
+        assert_eq!(tokens.remove(0), Token::Indent(0));
+        return tokens;
+    }
+
+    fn ident(name: &str) -> Token {
+        return Token::Identifier(name.to_string());
+    }
+
+    #[test]
+    fn errors_display_what_and_where() {
+        let err = TokenizerError::InvalidChar('@', 3);
+        assert_eq!(err.to_string(), "invalid character '@' at offset 3");
+        let err = TokenizerError::UnterminatedString(0);
+        assert_eq!(
+            err.to_string(),
+            "unterminated string literal starting at offset 0"
+        );
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        assert_eq!(
+            tokens("a # b + c\nd#e"),
+            [ident("a"), Token::Indent(0), ident("d")]
+        );
+        assert_eq!(
+            tokens("x = 1 # one\n# two\ny"),
+            [
+                ident("x"),
+                Token::Assignment,
+                Token::Int("1".to_string()),
+                Token::Indent(0),
+                ident("y")
+            ]
+        );
+    }
+
+    #[test]
+    fn line_endings_and_blank_lines() {
+        let lines = [ident("a"), Token::Indent(0), ident("b")];
+        assert_eq!(tokens("a\nb"), lines);
+        assert_eq!(tokens("a\r\nb"), lines);
+        // Only the last line break before code stands for an indent
+        assert_eq!(tokens("a\n\r\n\nb"), lines);
+    }
+
+    #[test]
+    fn spans_have_line_and_column() {
+        let tokens = lex("a = 1\nbc = 2");
+        let (span, tok) = &tokens[5];
+        assert_eq!(*tok, ident("bc"));
+        assert_eq!(
+            *span,
+            Span {
+                byte: 6,
+                line: 2,
+                col: 1
+            }
+        );
+        assert_eq!((tokens[6].0.line, tokens[6].0.col), (2, 4));
+    }
+
+    #[test]
+    fn string_escapes() {
+        assert_eq!(
+            tokens(r#""a\nb\t\"\\""#),
+            [Token::Str("a\nb\t\"\\".into())]
+        );
+        let err = Tokenizer::new(r#""a\qb""#.to_string())
+            .tokenize_spanned()
+            .unwrap_err();
+        assert!(matches!(err, TokenizerError::InvalidEscape('q', 2)));
+    }
+
+    fn str_literals(tokens: &[Token]) -> Vec<&StrLiteral> {
+        return tokens
+            .iter()
+            .filter_map(|tok| match tok {
+                Token::Str(literal) => Some(literal),
+                _ => None,
+            })
+            .collect();
+    }
+
+    /// A plain literal's text is a range of the tokenizer's own buffer of
+    /// the source, so pointing into that buffer shows nothing was copied or
+    /// allocated for it. One with escapes is resolved into a new string,
+    /// which points elsewhere
+    #[test]
+    fn plain_string_literals_borrow_the_source() {
+        let src = "x = \"h\u{e9}llo\"\ny = \"a\\tb\"\nz = \"\u{fc}n\"";
+        let mut tokenizer = Tokenizer::new(src.to_string());
+        let buffer = tokenizer.text.as_bytes().as_ptr_range();
+        let tokens = tokenizer.tokenize().expect("test source lexes");
+        let [plain, escaped, after] = str_literals(&tokens)[..] else {
+            panic!("expected three strings in {tokens:?}");
+        };
+        for (literal, text) in [(plain, "h\u{e9}llo"), (after, "\u{fc}n")] {
+            assert_eq!(literal.as_str(), text);
+            assert!(matches!(literal, StrLiteral::Borrowed(..)));
+            assert!(buffer.contains(&literal.as_str().as_ptr()));
+        }
+        assert_eq!(escaped.as_str(), "a\tb");
+        assert!(!buffer.contains(&escaped.as_str().as_ptr()));
+        // Part way into a source, ranges are still of what was lexed
+        let tokens = Tokenizer::new_at(src, 13, 2).tokenize().unwrap();
+        let literals = str_literals(&tokens);
+        assert_eq!(literals[1].as_str(), "\u{fc}n");
+    }
+
+    #[test]
+    fn int_literals_past_i32() {
+        assert_eq!(
+            tokens("9999999999"),
+            [Token::Int("9999999999".to_string())]
+        );
+    }
+
+    #[test]
+    fn malformed_number_is_an_error() {
+        for src in ["1__2", "123_", "12ab", "99999999999999999999"] {
+            let lexed = Tokenizer::new(src.to_string()).tokenize_spanned();
+            assert!(lexed.is_err(), "{src}");
+        }
+        let err = Tokenizer::new("x = 1__2".to_string())
+            .tokenize_spanned()
+            .unwrap_err();
+        assert!(matches!(err, TokenizerError::InvalidDigitSeparator(_, 4)));
+    }
+
+    #[test]
+    fn tokenize_collects_every_token() {
+        let tokens = Tokenizer::new("x = f(1)".to_string()).tokenize();
+        assert_eq!(
+            tokens.unwrap(),
+            [
+                Token::Indent(0),
+                ident("x"),
+                Token::Assignment,
+                ident("f"),
+                Token::LParen,
+                Token::Int("1".to_string()),
+                Token::RParen,
+                Token::Eof
+            ]
+        );
+        let err = Tokenizer::new("x = 1 @ 2".to_string())
+            .tokenize()
+            .unwrap_err();
+        assert!(matches!(err, TokenizerError::InvalidChar('@', 6)));
+    }
+
+    #[test]
+    fn keywords() {
+        let keywords = [
+            ("for", Token::For),
+            ("def", Token::Def),
+            ("mut", Token::Mut),
+            ("or", Token::Or),
+            ("and", Token::And),
+            ("not", Token::Not),
+            ("true", Token::True),
+            ("false", Token::False),
+            ("none", Token::None),
+            ("if", Token::If),
+            ("elif", Token::Elif),
+            ("else", Token::Else),
+            ("while", Token::While),
+            ("break", Token::Break),
+            ("continue", Token::Continue),
+            ("match", Token::Match),
+            ("case", Token::Case),
+            ("in", Token::In),
+            ("range", Token::Range),
+            ("return", Token::Return),
+            ("struct", Token::Struct),
+            ("self", Token::Self_),
+            ("enum", Token::Enum),
+            ("protocol", Token::Protocol),
+        ];
+        for (word, keyword) in keywords {
+            assert_eq!(Tokenizer::tokens_only(lex(word))[1], keyword);
+        }
+        for near_miss in ["iff", "_if", "If", "define", "ands", "nonee"] {
+            assert_eq!(
+                Tokenizer::tokens_only(lex(near_miss))[1],
+                ident(near_miss)
+            );
+        }
+    }
+
+    #[test]
+    fn colon_semicolon_arrow_and_walrus() {
+        assert_eq!(
+            tokens(": := ; -> =:"),
+            [
+                Token::Colon,
+                Token::Walrus,
+                Token::Semicolon,
+                Token::Arrow,
+                Token::ReverseWalrus
+            ]
+        );
+        assert_eq!(
+            tokens("x:=1;y->z"),
+            [
+                ident("x"),
+                Token::Walrus,
+                Token::Int("1".to_string()),
+                Token::Semicolon,
+                ident("y"),
+                Token::Arrow,
+                ident("z")
+            ]
+        );
+    }
+
+    #[test]
+    fn leading_whitespace_is_an_indent() {
+        let src = "if a:\n    if b:\n        c\n\td\ne";
+        let indents: Vec<Token> = tokens(src)
+            .into_iter()
+            .filter(|tok| matches!(tok, Token::Indent(_)))
+            .collect();
+        assert_eq!(
+            indents,
+            [
+                Token::Indent(1),
+                Token::Indent(2),
+                Token::Indent(1),
+                Token::Indent(0)
+            ]
+        );
+        // Whitespace after the start of a line only separates tokens
+        assert_eq!(tokens("a  =\t1"), tokens("a = 1"));
+        let lex_error = |src: &str| {
+            return Tokenizer::new(src.to_string())
+                .tokenize_spanned()
+                .unwrap_err();
+        };
+        assert!(matches!(
+            lex_error("a\n\t  b"),
+            TokenizerError::MixedIndentation(2)
+        ));
+        assert!(matches!(
+            lex_error("a\n   b"),
+            TokenizerError::InvalidIndentation(3, 2)
+        ));
+    }
+
+    #[test]
+    fn lenient_lexing_reports_every_error() {
+        let (tokens, errors) =
+            Tokenizer::new("1 @ 2 $ 3".to_string()).tokenize_lenient();
+        let ints: Vec<Token> = Tokenizer::tokens_only(tokens)
+            .into_iter()
+            .filter(|tok| matches!(tok, Token::Int(_)))
+            .collect();
+        assert_eq!(ints, ["1", "2", "3"].map(|i| Token::Int(i.to_string())));
+        assert!(matches!(
+            errors[..],
+            [
+                TokenizerError::InvalidChar('@', 2),
+                TokenizerError::InvalidChar('$', 6)
+            ]
+        ));
+    }
+
+    #[test]
+    fn less_and_greater_than() {
+        assert_eq!(
+            tokens("< <= > >="),
+            [Token::Lt, Token::LtEq, Token::Gt, Token::GtEq]
+        );
+        assert_eq!(
+            tokens("a<=b>c"),
+            [ident("a"), Token::LtEq, ident("b"), Token::Gt, ident("c")]
+        );
+    }
+
+    #[test]
+    fn render_shows_the_line_and_a_caret() {
+        let src = "x = 1\ny = 2 $ 3\nz = 4";
+        let err = Tokenizer::new(src.to_string())
+            .tokenize_spanned()
+            .unwrap_err();
+        assert_eq!(
+            err.with_src(src).render(),
+            "error: invalid character '$' at offset 12\n --> 2:7\n  |\n\
+             2 | y = 2 $ 3\n  |       ^"
+        );
+    }
+
+    #[test]
+    fn error_column_counts_chars() {
+        let src = "x = \"héllo\" @";
+        let err = Tokenizer::new(src.to_string())
+            .tokenize_spanned()
+            .unwrap_err();
+        assert_eq!(err.offset(), 12);
+        let rendered = err.with_src(src).render();
+        assert!(rendered.contains("--> 1:13\n"), "{rendered}");
+        let caret = format!("  | {}^", " ".repeat(12));
+        assert!(rendered.ends_with(&caret), "{rendered}");
+    }
+
+    #[test]
+    fn peek_then_advance_give_the_same_token() {
+        let mut lexer =
+            PeekableLexer::new(Tokenizer::new("a + 1".to_string()));
+        let mut seen = Vec::new();
+        while let Some(peeked) = lexer.peek() {
+            let peeked = peeked.as_ref().unwrap().clone();
+            let advanced = lexer.advance().unwrap().unwrap();
+            assert_eq!(peeked, advanced);
+            seen.push(advanced);
+        }
+        assert_eq!(seen, lex("a + 1"));
+        assert!(lexer.advance().is_none());
+    }
+
+    #[test]
+    fn lexing_from_part_way_keeps_absolute_spans() {
+        let tokens = Tokenizer::new_at("abc def", 4, 1).tokenize_spanned();
+        let (span, tok) = tokens.unwrap()[1].clone();
+        assert_eq!(tok, Token::Def);
+        assert_eq!(
+            span,
+            Span {
+                byte: 4,
+                line: 1,
+                col: 5
+            }
+        );
+        let tokens = Tokenizer::new_at("a\nbc d", 5, 2).tokenize_spanned();
+        assert_eq!(
+            tokens.unwrap()[1].0,
+            Span {
+                byte: 5,
+                line: 2,
+                col: 4
+            }
+        );
+    }
+
+    #[test]
+    fn crlf_and_lone_cr_end_lines() {
+        for (src, byte) in [("a\r\nb", 3), ("a\rb", 2), ("a\nb", 2)] {
+            let (span, tok) = lex(src)[3].clone();
+            assert_eq!(tok, ident("b"));
+            assert_eq!(
+                span,
+                Span {
+                    byte,
+                    line: 2,
+                    col: 1
+                },
+                "{src:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn tabs_separate_tokens() {
+        assert_eq!(tokens("a\t+\tb"), [ident("a"), Token::Add, ident("b")]);
+        assert_eq!(
+            tokens("a \u{c}\u{a0}+ b"),
+            [ident("a"), Token::Add, ident("b")]
+        );
+    }
+
+    #[test]
+    fn token_classes() {
+        let operators = [Token::Add, Token::LtEq, Token::And, Token::Pipe];
+        let keywords = [Token::If, Token::Def, Token::True, Token::Self_];
+        let literals = [
+            Token::Int("1".to_string()),
+            Token::Str("s".into()),
+            Token::Char('c'),
+            Token::None,
+        ];
+        let others = [ident("x"), Token::LParen, Token::Comma, Token::Eof];
+        for tok in operators {
+            assert!(tok.is_operator() && !tok.is_literal(), "{tok:?}");
+        }
+        for tok in keywords {
+            assert!(tok.is_keyword() && !tok.is_operator(), "{tok:?}");
+        }
+        for tok in literals {
+            assert!(tok.is_literal() && !tok.is_operator(), "{tok:?}");
+        }
+        for tok in others {
+            assert!(!tok.is_keyword() && !tok.is_operator(), "{tok:?}");
+            assert!(!tok.is_literal(), "{tok:?}");
+        }
+        // Word operators are keywords too
+        assert!(Token::And.is_keyword());
+    }
+
+    /// Re-lexes `old` after replacing `edit` with `insert`, checking it
+    /// comes out the same as lexing the edited source from scratch
+    fn assert_relex(old: &str, edit: Range<usize>, insert: &str) {
+        let (before, after) = (&old[..edit.start], &old[edit.end..]);
+        let new = format!("{before}{insert}{after}");
+        let relexed = Tokenizer::relex(&lex(old), edit, &new).unwrap();
+        assert_eq!(relexed, lex(&new));
+    }
+
+    #[test]
+    fn relex_after_inserting_and_deleting() {
+        let old = "x = 1 + 2\ny = x * 3\nprint(y)\n";
+        // Inside a token, between tokens, and across a line break
+        assert_relex(old, 4..4, "10");
+        assert_relex(old, 5..5, " - 4");
+        assert_relex(old, 9..9, "\nz = 0");
+        assert_relex(old, 4..8, "");
+        assert_relex(old, 8..12, "");
+        assert_relex(old, 0..old.len(), "");
+        // Into and out of a block
+        assert_relex("if a:\n    b\nc\n", 12..12, "    ");
+        assert_relex("if a:\n    b\n    c\n", 12..16, "");
+    }
+
+    #[test]
+    fn stream_ends_with_one_eof() {
+        for src in ["", "x = 1\n", "if a:\n    b\n"] {
+            let mut stream = TokenStream::new(Tokenizer::new(src.to_string()));
+            let tokens: Vec<Token> = stream
+                .by_ref()
+                .map(|tok| tok.expect("test source lexes").1)
+                .collect();
+            assert_eq!(tokens, Tokenizer::tokens_only(lex(src)));
+            let eofs = tokens.iter().filter(|tok| **tok == Token::Eof);
+            assert_eq!(eofs.count(), 1, "{src:?}");
+            assert_eq!(tokens.last(), Some(&Token::Eof));
+            assert!(stream.next().is_none());
+        }
+    }
+
+    #[test]
+    fn every_token_list_ends_in_one_eof() {
+        for src in ["", "\n", "x", "x = 1\n", "if a:\n    b\n\n", "# c"] {
+            let tokens = Tokenizer::tokens_only(lex(src));
+            let eofs = tokens.iter().filter(|tok| **tok == Token::Eof);
+            assert_eq!(eofs.count(), 1, "{src:?}");
+            assert_eq!(tokens.last(), Some(&Token::Eof), "{src:?}");
+        }
+    }
+
+    fn lex_error(src: &str) -> TokenizerError {
+        return Tokenizer::new(src.to_string())
+            .tokenize_spanned()
+            .expect_err("test source doesn't lex");
+    }
+
+    #[test]
+    fn exponent_floats() {
+        for literal in ["1e10", "2.5e-3", "6.02E+23"] {
+            assert_eq!(tokens(literal), [Token::Float(literal.to_string())]);
+        }
+        assert_eq!(float_literal_value("2.5e-3").unwrap(), 0.0025);
+        assert_eq!(run("print(1e3 == 1000.0)").unwrap(), "true\n");
+        let TokenizerError::InvalidNumericLiteral(literal, 0) =
+            lex_error("1e")
+        else {
+            panic!("`1e` has no exponent digits");
+        };
+        assert_eq!(literal, "1e");
+    }
+
+    #[test]
+    fn radix_prefixed_ints() {
+        let ints = [
+            ("0x1F", 31),
+            ("0xFF_FF", 65535),
+            ("0b1010", 10),
+            ("0o17", 15),
+        ];
+        for (literal, value) in ints {
+            assert_eq!(tokens(literal), [Token::Int(literal.to_string())]);
+            assert_eq!(int_literal_value(literal).unwrap(), value);
+        }
+        assert_eq!(run("print(0x10 + 0b1 + 0o10)").unwrap(), "25\n");
+        for malformed in ["0xZZ", "0xG", "0b12"] {
+            let TokenizerError::InvalidNumericLiteral(literal, 0) =
+                lex_error(malformed)
+            else {
+                panic!("{malformed} should be an invalid literal");
+            };
+            assert_eq!(literal, malformed);
+        }
+    }
+
+    #[test]
+    fn misplaced_digit_separators() {
+        for malformed in ["1_", "1__0", "1_.5", "0x1_"] {
+            let TokenizerError::InvalidDigitSeparator(literal, 0) =
+                lex_error(malformed)
+            else {
+                panic!("{malformed} has a misplaced underscore");
+            };
+            assert_eq!(literal, malformed);
+        }
+        assert_eq!(tokens("1_000"), [Token::Int("1_000".to_string())]);
+        assert_eq!(int_literal_value("1_000_000").unwrap(), 1_000_000);
+        // A leading underscore makes a name instead
+        assert_eq!(tokens("_1"), [ident("_1")]);
+    }
+
+    #[test]
+    fn char_literals() {
+        assert_eq!(tokens("'a'"), [Token::Char('a')]);
+        assert_eq!(tokens(r"'\n'"), [Token::Char('\n')]);
+        assert_eq!(tokens(r"'\''"), [Token::Char('\'')]);
+        assert_eq!(run("print('a')").unwrap(), "a\n");
+        assert!(matches!(
+            lex_error("''"),
+            TokenizerError::InvalidCharLiteral(0, 0)
+        ));
+        assert!(matches!(
+            lex_error("'ab'"),
+            TokenizerError::InvalidCharLiteral(2, 0)
+        ));
+        assert!(matches!(
+            lex_error("'a"),
+            TokenizerError::UnterminatedChar(0)
+        ));
+    }
+
+    fn indents(src: &str) -> Vec<Token> {
+        let mut tokens = Tokenizer::tokens_only(lex(src));
+        tokens.retain(|tok| matches!(tok, Token::Indent(_)));
+        return tokens;
+    }
+
+    #[test]
+    fn blank_and_comment_lines_emit_no_indent() {
+        assert_eq!(
+            indents("if x:\n    a\n\n  # c\n# c\n    b\n\n"),
+            [Token::Indent(0), Token::Indent(1), Token::Indent(1)]
+        );
+    }
+
+    #[test]
+    fn relex_around_blank_lines() {
+        let old = "if x:\n    a\n\n\n    b\n";
+        assert_relex(old, 10..11, "abc");
+        assert_relex(old, 11..11, "\n");
+        assert_relex(old, 11..12, "");
+        assert_relex(old, 12..12, "# c");
+    }
+
+    /// The tokens of `src` as next_tokens emits them, before
+    /// make_nice_indents has turned line breaks into indents
+    fn raw_tokens(src: &str) -> Vec<Token> {
+        let mut tokenizer = Tokenizer::new(src.to_string());
+        let mut tokens = Vec::new();
+        while tokenizer.r < tokenizer.src_len {
+            tokenizer
+                .next_tokens(&mut tokens)
+                .expect("test source lexes");
+        }
+        return tokens;
+    }
+
+    #[test]
+    fn newline_is_a_token() {
+        assert_eq!(
+            raw_tokens("a\nb"),
+            [ident("a"), Token::Newline, ident("b")]
+        );
+        assert_eq!(
+            Tokenizer::tokens_only(lex("a\nb")),
+            [
+                Token::Indent(0),
+                ident("a"),
+                Token::Indent(0),
+                ident("b"),
+                Token::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn crlf_is_one_newline() {
+        assert_eq!(
+            raw_tokens("a\r\nb"),
+            [ident("a"), Token::Newline, ident("b")]
+        );
+        assert_eq!(
+            raw_tokens("a\rb"),
+            [ident("a"), Token::Newline, ident("b")]
+        );
+    }
+
+    #[test]
+    fn blank_lines_emit_a_newline_each_but_one_indent() {
+        let newlines = [Token::Newline, Token::Newline, Token::Newline];
+        assert_eq!(raw_tokens("a\n\r\n\rb")[1..4], newlines);
+        assert_eq!(
+            indents("a\n\r\n\rb"),
+            [Token::Indent(0), Token::Indent(0)]
+        );
+    }
+}
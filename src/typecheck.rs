@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::ast::{BlockStmt, Node};
+use crate::token::Token;
+
+/// The types the checker can infer. Anything it can't work out is left as
+/// `None` by the nodes and never reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Int,
+    Float,
+    Bool,
+    Str,
+    Char,
+}
+
+impl Type {
+    /// Reads a type annotation, ignoring case so `int` and `Int` agree.
+    /// Names the checker doesn't know about give `None`
+    pub fn from_annotation(annotation: &str) -> Option<Type> {
+        return match annotation.to_lowercase().as_str() {
+            "int" => Some(Type::Int),
+            "float" => Some(Type::Float),
+            "bool" => Some(Type::Bool),
+            "str" => Some(Type::Str),
+            "char" => Some(Type::Char),
+            _ => None,
+        };
+    }
+    pub fn is_numeric(&self) -> bool {
+        return matches!(self, Type::Int | Type::Float);
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Type::Int => "int",
+            Type::Float => "float",
+            Type::Bool => "bool",
+            Type::Str => "str",
+            Type::Char => "char",
+        };
+        return write!(f, "{s}");
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TypeError {
+    AnnotationMismatch {
+        name: String,
+        annotation: Type,
+        found: Type,
+    },
+    NonNumericOperand {
+        op: Token,
+        found: Type,
+    },
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Self::AnnotationMismatch {
+                name,
+                annotation,
+                found,
+            } => write!(
+                f,
+                "{name} is annotated as {annotation} but assigned a {found}"
+            ),
+            Self::NonNumericOperand { op, found } => {
+                write!(f, "{op} expects numbers but got a {found}")
+            }
+        };
+    }
+}
+
+/// State for one pass of the checker: the inferred type of every name seen
+/// so far and every error found
+#[derive(Default)]
+pub struct TypeChecker {
+    vars: HashMap<String, Type>,
+    errors: Vec<TypeError>,
+}
+
+impl TypeChecker {
+    pub fn get(&self, name: &str) -> Option<Type> {
+        return self.vars.get(name).copied();
+    }
+    pub fn set(&mut self, name: String, ty: Option<Type>) {
+        match ty {
+            Some(ty) => self.vars.insert(name, ty),
+            // A name whose new value can't be inferred is unknown from here
+            None => self.vars.remove(&name),
+        };
+    }
+    pub fn error(&mut self, error: TypeError) {
+        self.errors.push(error);
+    }
+    /// Runs `check` with its own copy of the names in scope, so bindings it
+    /// makes, like function arguments, don't leak out
+    pub fn scoped(&mut self, check: impl FnOnce(&mut TypeChecker)) {
+        let vars = self.vars.clone();
+        check(self);
+        self.vars = vars;
+    }
+}
+
+/// Checks annotated assignments against the types of their values and that
+/// arithmetic is done on numbers, reporting every problem found rather than
+/// stopping at the first
+pub fn typecheck(program: &BlockStmt) -> Result<(), Vec<TypeError>> {
+    let mut checker = TypeChecker::default();
+    program.check_types(&mut checker);
+    if checker.errors.is_empty() {
+        return Ok(());
+    }
+    return Err(checker.errors);
+}
+
+/// The type a binary operator produces, reporting operands arithmetic
+/// can't be done on
+pub fn binary_type(
+    checker: &mut TypeChecker,
+    op: &Token,
+    l: Option<Type>,
+    r: Option<Type>,
+) -> Option<Type> {
+    if let Token::Eq
+    | Token::NotEq
+    | Token::Lt
+    | Token::LtEq
+    | Token::Gt
+    | Token::GtEq = op
+    {
+        return Some(Type::Bool);
+    }
+    // Strings only support concatenation, and repetition by an int
+    match (op, l, r) {
+        (Token::Add, Some(Type::Str), Some(Type::Str))
+        | (Token::Mul, Some(Type::Str), Some(Type::Int))
+        | (Token::Mul, Some(Type::Int), Some(Type::Str)) => {
+            return Some(Type::Str);
+        }
+        _ => {}
+    }
+    for found in [l, r].into_iter().flatten() {
+        if !found.is_numeric() {
+            checker.error(TypeError::NonNumericOperand {
+                op: op.clone(),
+                found,
+            });
+        }
+    }
+    return match (l?, r?) {
+        // A negative exponent makes a float, which isn't known until runtime
+        (Type::Int, Type::Int) if *op == Token::Pow => None,
+        (Type::Int, Type::Int) => Some(Type::Int),
+        (l, r) if l.is_numeric() && r.is_numeric() => Some(Type::Float),
+        _ => None,
+    };
+}
+
+/// The type a prefix operator produces
+pub fn unary_type(
+    checker: &mut TypeChecker,
+    op: &Token,
+    operand: Option<Type>,
+) -> Option<Type> {
+    if let Token::Bang | Token::Not = op {
+        return Some(Type::Bool);
+    }
+    let found = operand?;
+    if !found.is_numeric() {
+        checker.error(TypeError::NonNumericOperand {
+            op: op.clone(),
+            found,
+        });
+        return None;
+    }
+    return Some(found);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::token::Tokenizer;
+
+    fn check(src: &str) -> Result<(), Vec<TypeError>> {
+        let program = Parser::from_lexer(Tokenizer::new(src.to_string()))
+            .parse_program()
+            .expect("test source parses");
+        return typecheck(&program);
+    }
+
+    #[test]
+    fn well_typed_program_passes() {
+        let src = "x: int = 1 + 2\ny: float = x * 1.5\ns: str = \"a\" * x\n\
+                   def f(a: int) -> int:\n    return a + 1";
+        assert_eq!(check(src), Ok(()));
+    }
+
+    #[test]
+    fn mismatched_annotation_fails() {
+        let errors = check("x: int = \"a\"\ny = x + 1\nz = 1 + \"b\"");
+        let messages: Vec<String> =
+            errors.unwrap_err().iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "x is annotated as int but assigned a str",
+                "+ expects numbers but got a str"
+            ]
+        );
+    }
+}